@@ -0,0 +1,215 @@
+"use strict";
+
+// the dashboard is served from /dashboard/, the api from /v1/ next to it
+const API = new URL("../v1/", location.href);
+const PAGE_SIZE = 50;
+const REFRESH_MS = 30000;
+const TIMELINE_EPOCHS = 20;
+const KEY_STORAGE = "poolstats.api_key";
+
+const $ = (id) => document.getElementById(id);
+const fmt = (n) => (n == null ? "-" : Number(n).toLocaleString());
+
+let clock = null;
+let cursors = [null];
+let page = 0;
+
+async function api(path, options = {}) {
+  const headers = { ...(options.headers || {}) };
+  const key = localStorage.getItem(KEY_STORAGE);
+  if (key) headers["x-api-key"] = key;
+  if (options.body) headers["content-type"] = "application/json";
+  const resp = await fetch(new URL(path, API), { ...options, headers });
+  const body = await resp.json().catch(() => ({}));
+  if (!resp.ok) throw new Error(body.message || `${resp.status} ${resp.statusText}`);
+  return body;
+}
+
+function setStatus(text) {
+  $("status").textContent = text;
+}
+
+function countdown(at) {
+  let secs = Math.max(0, at - Math.floor(Date.now() / 1000));
+  const days = Math.floor(secs / 86400);
+  secs %= 86400;
+  const hms = [Math.floor(secs / 3600), Math.floor((secs % 3600) / 60), secs % 60]
+    .map((v) => String(v).padStart(2, "0"))
+    .join(":");
+  return days > 0 ? `${days}d ${hms}` : hms;
+}
+
+function renderClock() {
+  if (!clock) return;
+  $("clock-epoch").textContent = fmt(clock.epoch);
+  $("clock-layer").textContent = `layer ${fmt(clock.layer)}`;
+  $("clock-registration").textContent = clock.registration_open
+    ? "open"
+    : countdown(clock.next_registration_at);
+  $("clock-registration-layer").textContent = clock.registration_open
+    ? `next opens in ${countdown(clock.next_registration_at)}`
+    : `opens at layer ${fmt(clock.next_registration_layer)}`;
+  $("clock-epoch-end").textContent = countdown(clock.next_epoch_at);
+  $("clock-epoch-layer").textContent = `starts at layer ${fmt(clock.next_epoch_layer)}`;
+}
+
+async function loadClock() {
+  clock = (await api("clock")).data;
+  renderClock();
+}
+
+async function loadOverview() {
+  const { data, partial } = await api("overview");
+  $("overview-epoch").textContent = `epoch ${data.epoch}${partial ? ", partial" : ""}`;
+  const node = data.node;
+  $("overview-unsynced").hidden = !data.unsynced;
+  $("overview-unsynced").textContent = node
+    ? `node is not synced (layer ${fmt(node.synced_layer)} of ${fmt(node.top_layer)}, ${fmt(node.connected_peers)} peers), counts may be incomplete`
+    : "node status unknown, counts may be incomplete";
+  $("init-count").textContent = fmt(data.init_posted.count);
+  $("init-units").textContent = fmt(data.init_posted.num_units);
+  $("initializing-count").textContent = fmt(data.initializing.count);
+  $("initializing-units").textContent = fmt(data.initializing.num_units);
+  for (const [prefix, item] of [["reg", data.registerd], ["act", data.actived]]) {
+    $(`${prefix}-count`).textContent = fmt(item.current.count);
+    $(`${prefix}-units`).textContent = fmt(item.current.num_units);
+    $(`${prefix}-next-count`).textContent = fmt(item.next.count);
+    $(`${prefix}-next-units`).textContent = fmt(item.next.num_units);
+  }
+}
+
+function svg(tag, attrs, text) {
+  const el = document.createElementNS("http://www.w3.org/2000/svg", tag);
+  for (const [k, v] of Object.entries(attrs)) el.setAttribute(k, v);
+  if (text != null) el.textContent = text;
+  return el;
+}
+
+// grouped bars, registered next to active for every epoch
+function drawChart(target, entries, value) {
+  const el = $(target);
+  el.replaceChildren();
+  const [width, height, left, bottom] = [600, 220, 48, 20];
+  const max = Math.max(1, ...entries.flatMap((e) => [value(e.registerd), value(e.actived)]));
+  const slot = (width - left) / Math.max(1, entries.length);
+  const bar = Math.max(1, slot * 0.4);
+  const y = (v) => (height - bottom) * (1 - v / max);
+  for (const tick of [0, 0.5, 1]) {
+    const v = Math.round(max * tick);
+    el.append(svg("text", { x: left - 6, y: y(v) + 3, "text-anchor": "end" }, fmt(v)));
+    el.append(svg("line", { x1: left, x2: width, y1: y(v), y2: y(v), stroke: "#dde2e7" }));
+  }
+  entries.forEach((e, i) => {
+    const x = left + i * slot + slot * 0.1;
+    for (const [cls, item, dx] of [["registered", e.registerd, 0], ["active", e.actived, bar]]) {
+      const v = value(item);
+      const rect = svg("rect", {
+        class: cls,
+        x: x + dx,
+        y: y(v),
+        width: bar,
+        height: height - bottom - y(v),
+      });
+      rect.append(svg("title", {}, `epoch ${e.epoch} ${cls}: ${fmt(v)}`));
+      el.append(rect);
+    }
+    if (entries.length <= 12 || i % Math.ceil(entries.length / 12) === 0) {
+      el.append(svg("text", { x: x + bar, y: height - 6, "text-anchor": "middle" }, e.epoch));
+    }
+  });
+}
+
+async function loadTimeline() {
+  const entries = (await api("timeline")).data.slice(-TIMELINE_EPOCHS);
+  drawChart("chart-count", entries, (item) => item.count);
+  drawChart("chart-units", entries, (item) => item.num_units);
+}
+
+function filters() {
+  const form = new FormData($("filters"));
+  const req = { limit: PAGE_SIZE, sort_by: form.get("sort_by"), order: form.get("order") };
+  for (const name of ["status", "id_prefix", "coinbase"]) {
+    const v = form.get(name).trim();
+    if (v) req[name] = v;
+  }
+  for (const name of ["min_num_units", "max_num_units"]) {
+    const v = form.get(name);
+    if (v !== "") req[name] = Number(v);
+  }
+  return req;
+}
+
+function cell(text, cls) {
+  const td = document.createElement("td");
+  td.textContent = text;
+  if (cls) {
+    td.className = cls;
+    td.title = text;
+  }
+  return td;
+}
+
+async function loadNodes() {
+  const error = $("nodes-error");
+  const req = { ...filters(), cursor: cursors[page] };
+  let data;
+  try {
+    data = (await api("nodes_info", { method: "POST", body: JSON.stringify(req) })).data;
+    error.hidden = true;
+  } catch (e) {
+    error.textContent = `nodes: ${e.message}`;
+    error.hidden = false;
+    return;
+  }
+  const rows = data.data.map((node) => {
+    const tr = document.createElement("tr");
+    const atx = node.atx.atx_id ? node.atx : null;
+    tr.append(
+      cell(node.id, "id"),
+      cell(fmt(node.num_units)),
+      cell(node.status),
+      cell(node.registerations.map((r) => r.round_id).join(", ")),
+      cell(atx ? atx.atx_id : "", "id"),
+      cell(atx ? fmt(atx.effective_num_units) : ""),
+      cell(atx ? atx.coinbase : "", "id"),
+    );
+    return tr;
+  });
+  $("nodes").replaceChildren(...rows);
+  $("nodes-total").textContent = `${fmt(data.total)} matching`;
+  cursors[page + 1] = data.next_cursor;
+  $("page").textContent = `page ${page + 1} of ${Math.max(1, Math.ceil(data.total / PAGE_SIZE))}`;
+  $("prev").disabled = page === 0;
+  $("next").disabled = !data.next_cursor;
+}
+
+async function refresh() {
+  const results = await Promise.allSettled([loadClock(), loadOverview(), loadTimeline()]);
+  const failed = results.filter((r) => r.status === "rejected");
+  failed.forEach((r) => console.warn(r.reason));
+  setStatus(failed.length ? `refresh failed: ${failed[0].reason.message}` : `updated ${new Date().toLocaleTimeString()}`);
+}
+
+$("filters").key.value = localStorage.getItem(KEY_STORAGE) || "";
+$("filters").addEventListener("submit", (ev) => {
+  ev.preventDefault();
+  const key = ev.target.key.value.trim();
+  if (key) localStorage.setItem(KEY_STORAGE, key);
+  else localStorage.removeItem(KEY_STORAGE);
+  cursors = [null];
+  page = 0;
+  loadNodes();
+});
+$("prev").addEventListener("click", () => {
+  page = Math.max(0, page - 1);
+  loadNodes();
+});
+$("next").addEventListener("click", () => {
+  page += 1;
+  loadNodes();
+});
+
+refresh();
+loadNodes();
+setInterval(refresh, REFRESH_MS);
+setInterval(renderClock, 1000);
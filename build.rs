@@ -0,0 +1,15 @@
+// compiles the vendored node protos without needing protoc installed
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let protos = [
+        "proto/spacemesh/v1/mesh.proto",
+        "proto/spacemesh/v1/node.proto",
+        "proto/spacemesh/v1/smesher.proto",
+    ];
+    println!("cargo:rerun-if-changed=proto");
+    println!("cargo:rerun-if-changed=migrations");
+    let fds = protox::compile(protos, ["proto"])?;
+    tonic_build::configure()
+        .build_server(true)
+        .compile_fds(fds)?;
+    Ok(())
+}
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS init_snapshots (
+    epoch INT NOT NULL,
+    count INT NOT NULL,
+    num_units INT NOT NULL,
+    PRIMARY KEY (epoch)
+) WITHOUT ROWID;
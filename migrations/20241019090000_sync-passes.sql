@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS sync_passes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    epoch INT NOT NULL,
+    layer INT NOT NULL,
+    keys INT NOT NULL,
+    started_at INT NOT NULL,
+    finished_at INT NOT NULL
+);
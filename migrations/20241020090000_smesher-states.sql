@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS smesher_states (
+    id CHAR(64) NOT NULL,
+    state VARCHAR NOT NULL,
+    smeshing BOOLEAN NOT NULL,
+    num_labels_written INT NOT NULL,
+    num_units INT NOT NULL,
+    updated_at INT NOT NULL,
+    PRIMARY KEY (id)
+) WITHOUT ROWID;
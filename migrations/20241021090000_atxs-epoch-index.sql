@@ -0,0 +1,2 @@
+-- Add migration script here
+CREATE INDEX IF NOT EXISTS atxs_epoch_id ON atxs (epoch, id);
@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS cache_generation (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    generation INT NOT NULL
+);
+INSERT OR IGNORE INTO cache_generation (id, generation) VALUES (1, 0);
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE smesher_states ADD COLUMN node VARCHAR NOT NULL DEFAULT '';
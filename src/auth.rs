@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::JsonRejection, Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_now,
+    error::{ApiError, ApiResponse},
+    metrics::TrackDbError,
+    DBHandler, Shared,
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// ordered, a key grants its own scope and every scope below it
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    clap::ValueEnum,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// aggregate pool numbers
+    Public,
+    /// per-node ids and coinbases
+    Private,
+    /// key management
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Public => "public",
+            Scope::Private => "private",
+            Scope::Admin => "admin",
+        }
+    }
+
+    fn parse(scope: &str) -> Option<Self> {
+        match scope {
+            "public" => Some(Scope::Public),
+            "private" => Some(Scope::Private),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow, ToSchema)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub scope: String,
+    pub created_at: i64,
+    pub revoked_at: Option<i64>,
+}
+
+impl ApiKey {
+    pub fn scope(&self) -> Option<Scope> {
+        Scope::parse(&self.scope)
+    }
+}
+
+/// returned once on creation, only the hash is stored
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CreatedApiKey {
+    pub id: i64,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scope: Scope,
+}
+
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+impl DBHandler {
+    pub async fn create_api_key(
+        &self,
+        name: String,
+        scope: Scope,
+    ) -> Result<CreatedApiKey, sqlx::Error> {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let key = format!("ps_{}", hex::encode(secret));
+        let result = sqlx::query(
+            "INSERT INTO api_keys (name, key_hash, scope, created_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(name)
+        .bind(hash_key(&key))
+        .bind(scope.as_str())
+        .bind(unix_now())
+        .execute(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(CreatedApiKey {
+            id: result.last_insert_rowid(),
+            key,
+        })
+    }
+
+    /// returns whether an active key was revoked
+    pub async fn revoke_api_key(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("UPDATE api_keys SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL")
+                .bind(unix_now())
+                .bind(id)
+                .execute(&self.poolstats)
+                .await
+                .track("poolstats")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let result = sqlx::query_as(
+            "SELECT id, name, scope, created_at, revoked_at FROM api_keys ORDER BY id",
+        )
+        .fetch_all(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(result)
+    }
+
+    pub async fn find_api_key(&self, key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        let result = sqlx::query_as(
+            "SELECT id, name, scope, created_at, revoked_at FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(hash_key(key))
+        .fetch_optional(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(result)
+    }
+}
+
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok();
+    }
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// anonymous requests only reach public routes, a presented key must always be valid
+pub async fn require_scope(
+    State((shared, scope)): State<(Arc<Shared>, Scope)>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let unauthorized = || {
+        ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "missing or invalid api key",
+        )
+    };
+    match presented_key(req.headers()) {
+        Some(key) => {
+            let api_key = shared
+                .db_handler
+                .find_api_key(key)
+                .await?
+                .ok_or_else(unauthorized)?;
+            if api_key.scope().is_none_or(|granted| granted < scope) {
+                return Err(ApiError::new(
+                    StatusCode::FORBIDDEN,
+                    "forbidden",
+                    format!("api key lacks the {} scope", scope.as_str()),
+                ));
+            }
+            req.extensions_mut().insert(api_key);
+        }
+        None if scope == Scope::Public => {}
+        None => return Err(unauthorized()),
+    }
+    Ok(next.run(req).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, body = ApiKeysResponse),
+        (status = 401, description = "missing or unknown key", body = ErrorBody),
+        (status = 403, description = "key lacks the admin scope", body = ErrorBody),
+    )
+)]
+pub async fn list_keys_handler(
+    State(shared): State<Arc<Shared>>,
+) -> Result<ApiResponse<Vec<ApiKey>>, ApiError> {
+    let keys = shared.db_handler.list_api_keys().await?;
+    Ok(ApiResponse::new(keys, false))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys",
+    tag = "admin",
+    request_body = CreateApiKeyRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "the key is only shown here", body = CreatedApiKeyResponse),
+        (status = 401, description = "missing or unknown key", body = ErrorBody),
+        (status = 403, description = "key lacks the admin scope", body = ErrorBody),
+        (status = 422, description = "malformed request body", body = ErrorBody),
+    )
+)]
+pub async fn create_key_handler(
+    State(shared): State<Arc<Shared>>,
+    req: Result<Json<CreateApiKeyRequest>, JsonRejection>,
+) -> Result<ApiResponse<CreatedApiKey>, ApiError> {
+    let Json(req) = req?;
+    let created = shared
+        .db_handler
+        .create_api_key(req.name, req.scope)
+        .await?;
+    Ok(ApiResponse::new(created, false))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/keys/{id}",
+    tag = "admin",
+    params(("id" = i64, Path, description = "id of the key to revoke")),
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "id of the revoked key", body = RevokedApiKeyResponse),
+        (status = 401, description = "missing or unknown key", body = ErrorBody),
+        (status = 403, description = "key lacks the admin scope", body = ErrorBody),
+        (status = 404, description = "no active key with the id", body = ErrorBody),
+    )
+)]
+pub async fn revoke_key_handler(
+    State(shared): State<Arc<Shared>>,
+    Path(id): Path<i64>,
+) -> Result<ApiResponse<i64>, ApiError> {
+    if !shared.db_handler.revoke_api_key(id).await? {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "key_not_found",
+            format!("no active api key {}", id),
+        ));
+    }
+    Ok(ApiResponse::new(id, false))
+}
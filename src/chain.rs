@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, QueryBuilder, Sqlite};
 
 use crate::{
-    poolstats::{AtxInfo, Key, Registeration},
+    metrics::TrackDbError,
+    poolstats::{
+        AtxInfo, Cursor, GeneralRequest, Key, NodeStatus, Registeration, SortBy, SortOrder,
+    },
     DBHandler,
 };
 
@@ -37,14 +40,95 @@ impl InnerAtxInfo {
     }
 }
 
+/// an initialized key with what the cache holds of it for one epoch
+#[derive(Debug, Clone, PartialEq, FromRow)]
+struct InnerNode {
+    id: Vec<u8>,
+    num_units: i64,
+    registered: bool,
+    epoch: Option<i64>,
+    atx_id: Option<String>,
+    effective_num_units: Option<i64>,
+    coinbase: Option<String>,
+}
+
+impl InnerNode {
+    fn into_node(self) -> (Key, NodeStatus, AtxInfo) {
+        let key = Key {
+            id: hex::encode(self.id),
+            num_units: self.num_units,
+        };
+        let status = NodeStatus::of(self.atx_id.is_some(), self.registered);
+        let atx = AtxInfo {
+            epoch: self.epoch.unwrap_or_default(),
+            atx_id: self.atx_id.unwrap_or_default(),
+            effective_num_units: self.effective_num_units.unwrap_or_default(),
+            coinbase: self.coinbase.unwrap_or_default(),
+        };
+        (key, status, atx)
+    }
+}
+
+/// `post` joined with the atxs of `epoch` and the registrations of `round_id` from the cache,
+/// narrowed by the filters of `req`
+fn push_nodes(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    req: &GeneralRequest,
+    epoch: i64,
+    round_id: &str,
+) {
+    query
+        .push(" FROM post p LEFT JOIN cache.atxs a ON a.epoch = ")
+        .push_bind(epoch)
+        .push(" AND a.id = lower(hex(p.id)) LEFT JOIN cache.poet_registration r ON r.round_id = ")
+        .push_bind(round_id.to_string())
+        .push(" AND r.id = lower(hex(p.id)) WHERE 1");
+    if let Some(min_num_units) = req.min_num_units {
+        query.push(" AND p.num_units >= ").push_bind(min_num_units);
+    }
+    if let Some(max_num_units) = req.max_num_units {
+        query.push(" AND p.num_units <= ").push_bind(max_num_units);
+    }
+    if let Some(prefix) = &req.id_prefix {
+        query
+            .push(" AND hex(p.id) LIKE ")
+            .push_bind(format!("{}%", prefix.to_uppercase()));
+    }
+    match req.status {
+        Some(NodeStatus::Active) => query.push(" AND a.id IS NOT NULL"),
+        Some(NodeStatus::Registered) => query.push(" AND a.id IS NULL AND r.id IS NOT NULL"),
+        Some(NodeStatus::Missing) => query.push(" AND a.id IS NULL AND r.id IS NULL"),
+        None => query,
+    };
+    if let Some(coinbase) = &req.coinbase {
+        query
+            .push(" AND lower(a.coinbase) = ")
+            .push_bind(coinbase.to_lowercase());
+    }
+}
+
+/// ids come from callers as hex, a bad one is reported rather than panicking
+pub fn decode_id(id: &str) -> Result<Vec<u8>, sqlx::Error> {
+    hex::decode(id).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
 impl DBHandler {
-    pub async fn get_init_keys(&self, limit: i64, offset: i64) -> Result<Vec<Key>, sqlx::Error> {
-        let result: Vec<InnerKey> =
-            sqlx::query_as("SELECT id, num_units FROM post LIMIT $1 OFFSET $2")
-                .bind(limit)
-                .bind(offset)
-                .fetch_all(&self.local)
-                .await?;
+    /// page through `post` ordered by id, starting right after the `after` id; an `after` that is
+    /// not hex is a decode error rather than a restart from the first key
+    pub async fn get_init_keys(
+        &self,
+        limit: i64,
+        after: Option<String>,
+    ) -> Result<Vec<Key>, sqlx::Error> {
+        let after = after.as_deref().map(decode_id).transpose()?;
+        let result: Vec<InnerKey> = sqlx::query_as(
+            "SELECT id, num_units FROM post WHERE ($1 IS NULL OR id > $1) ORDER BY id LIMIT $2",
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.local)
+        .await
+        .track("local")?;
         let result = result
             .into_iter()
             .map(|k| Key {
@@ -55,17 +139,109 @@ impl DBHandler {
         Ok(result)
     }
 
+    /// up to `limit` initialized keys matching every filter of `req` with their status for
+    /// `epoch` and `round_id`, in the order of `req` and right after `cursor`, or after
+    /// `req.offset` rows without one; needs the cache attached to `local`
+    pub async fn get_nodes_page(
+        &self,
+        req: &GeneralRequest,
+        epoch: i64,
+        round_id: &str,
+        cursor: Option<&Cursor>,
+        limit: i64,
+    ) -> Result<Vec<(Key, NodeStatus, AtxInfo)>, sqlx::Error> {
+        let mut query = QueryBuilder::new(
+            "SELECT p.id, p.num_units, r.id IS NOT NULL AS registered, a.epoch, a.atx_id, a.effective_num_units, a.coinbase",
+        );
+        push_nodes(&mut query, req, epoch, round_id);
+        let (after, direction) = match req.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        // sorting by id alone keeps to the primary key of `post`
+        let column = match req.sort_by {
+            SortBy::Id => None,
+            SortBy::NumUnits => Some("p.num_units"),
+            SortBy::EffectiveNumUnits => Some("COALESCE (a.effective_num_units, 0)"),
+        };
+        if let Some(cursor) = cursor {
+            let id = decode_id(&cursor.id)?;
+            match column {
+                Some(column) => query
+                    .push(format!(" AND ({}, p.id) {} (", column, after))
+                    .push_bind(cursor.value)
+                    .push(", ")
+                    .push_bind(id)
+                    .push(")"),
+                None => query.push(format!(" AND p.id {} ", after)).push_bind(id),
+            };
+        }
+        match column {
+            Some(column) => query.push(format!(
+                " ORDER BY {} {}, p.id {}",
+                column, direction, direction
+            )),
+            None => query.push(format!(" ORDER BY p.id {}", direction)),
+        };
+        let offset = match cursor {
+            Some(_) => 0,
+            None => req.offset.max(0),
+        };
+        query
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let result: Vec<InnerNode> = query
+            .build_query_as()
+            .fetch_all(&self.local)
+            .await
+            .track("local")?;
+        Ok(result.into_iter().map(InnerNode::into_node).collect())
+    }
+
+    /// how many initialized keys match every filter of `req`
+    pub async fn count_nodes(
+        &self,
+        req: &GeneralRequest,
+        epoch: i64,
+        round_id: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let mut query = QueryBuilder::new("SELECT COUNT (*)");
+        push_nodes(&mut query, req, epoch, round_id);
+        query
+            .build_query_scalar()
+            .fetch_one(&self.local)
+            .await
+            .track("local")
+    }
+
+    pub async fn get_init_key(&self, id: &str) -> Result<Option<Key>, sqlx::Error> {
+        let result: Option<InnerKey> =
+            sqlx::query_as("SELECT id, num_units FROM post WHERE id = $1")
+                .bind(decode_id(id)?)
+                .fetch_optional(&self.local)
+                .await
+                .track("local")?;
+        Ok(result.map(|k| Key {
+            id: hex::encode(k.id),
+            num_units: k.num_units,
+        }))
+    }
+
     pub async fn count_initialzed(&self) -> Result<i64, sqlx::Error> {
         let result = sqlx::query_scalar("SELECT COUNT (*) FROM post")
             .fetch_one(&self.local)
-            .await?;
+            .await
+            .track("local")?;
         Ok(result)
     }
 
     pub async fn inited_num_units(&self) -> Result<i64, sqlx::Error> {
-        let result = sqlx::query_scalar("SELECT SUM (num_units) FROM post")
+        let result = sqlx::query_scalar("SELECT COALESCE (SUM (num_units), 0) FROM post")
             .fetch_one(&self.local)
-            .await?;
+            .await
+            .track("local")?;
         Ok(result)
     }
 
@@ -77,10 +253,11 @@ impl DBHandler {
         let result = sqlx::query_as(
             "SELECT address, round_id, round_end FROM poet_registration WHERE id = $1 AND round_id = $2",
         )
-        .bind(hex::decode(id).unwrap())
+        .bind(decode_id(&id)?)
         .bind(round_id)
         .fetch_all(&self.local)
-        .await?;
+        .await
+        .track("local")?;
         Ok(result)
     }
 
@@ -92,10 +269,19 @@ impl DBHandler {
         let result = sqlx::query_as::<_, InnerAtxInfo>(
             "SELECT epoch, id AS atx_id, effective_num_units, coinbase FROM atxs WHERE pubkey = $1 AND epoch = $2",
         )
-        .bind(hex::decode(id).unwrap())
+        .bind(decode_id(&id)?)
         .bind(epoch)
         .fetch_one(&self.chain)
-        .await?;
+        .await
+        .track("chain")?;
         Ok(result.to_atx())
     }
+
+    /// highest layer the node has written to the chain db, none when it holds no layers
+    pub async fn latest_chain_layer(&self) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX (id) FROM layers")
+            .fetch_one(&self.chain)
+            .await
+            .track("chain")
+    }
 }
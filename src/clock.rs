@@ -0,0 +1,170 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use utoipa::ToSchema;
+
+use crate::{
+    config::Network,
+    error::{ApiError, ApiResponse},
+    metrics::METRICS,
+    rpc::RpcHandler,
+    Shared,
+};
+
+/// defaults of the `[network]` config section
+pub const LAYERS_PER_EPOCH: i64 = 4032;
+/// layer within an epoch where the next poet round starts taking registrations
+pub const POET_REGISTRATION_OFFSET: i64 = 2760;
+/// seconds per layer, used to estimate when upcoming layers start
+pub const LAYER_DURATION: i64 = 5 * 60;
+pub const CLOCK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub struct ClockState {
+    pub epoch: i64,
+    pub layer: i64,
+    /// unix time of the rpc answer
+    pub refreshed_at: i64,
+}
+
+impl ClockState {
+    /// `layer` as answered now, in the epoch it falls into
+    pub fn at_layer(layer: i64, network: &Network) -> Self {
+        Self {
+            epoch: layer / network.layers_per_epoch,
+            layer,
+            refreshed_at: unix_now(),
+        }
+    }
+
+    /// registrations for the round numbered after the current epoch are open
+    pub fn registration_open(&self, network: &Network) -> bool {
+        self.layer >= self.epoch * network.layers_per_epoch + network.poet_registration_offset
+    }
+
+    pub fn age(&self) -> i64 {
+        (unix_now() - self.refreshed_at).max(0)
+    }
+
+    /// first layer of the next epoch
+    pub fn next_epoch_layer(&self, network: &Network) -> i64 {
+        (self.epoch + 1) * network.layers_per_epoch
+    }
+
+    /// layer the next registration window opens at, in the next epoch once this one is open
+    pub fn next_registration_layer(&self, network: &Network) -> i64 {
+        let layer = self.epoch * network.layers_per_epoch + network.poet_registration_offset;
+        if self.layer >= layer {
+            layer + network.layers_per_epoch
+        } else {
+            layer
+        }
+    }
+
+    /// unix time `layer` is expected to start at, assuming the current one started when refreshed
+    pub fn estimate(&self, layer: i64, network: &Network) -> i64 {
+        self.refreshed_at + (layer - self.layer) * network.layer_duration_secs
+    }
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// last epoch and layer seen from the node, read by handlers without any io
+#[derive(Debug, Default)]
+pub struct ChainClock {
+    state: RwLock<Option<ClockState>>,
+}
+
+impl ChainClock {
+    pub fn get(&self) -> Option<ClockState> {
+        *self.state.read().unwrap()
+    }
+
+    pub fn current(&self) -> Result<ClockState, ApiError> {
+        self.get().ok_or_else(|| {
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "clock_unavailable",
+                "chain clock not refreshed yet",
+            )
+        })
+    }
+
+    /// asks for the layer only, a separate epoch request could be answered by another endpoint
+    /// after a failover and not match it
+    pub async fn refresh(&self, rpc: &RpcHandler, network: &Network) -> anyhow::Result<ClockState> {
+        let layer = rpc.get_layer().await?.layernum.number;
+        let state = ClockState::at_layer(layer, network);
+        self.set(state);
+        Ok(state)
+    }
+
+    pub fn set(&self, state: ClockState) {
+        *self.state.write().unwrap() = Some(state);
+        METRICS.current_epoch.set(state.epoch);
+        METRICS.current_layer.set(state.layer);
+    }
+}
+
+pub async fn run(shared: Arc<Shared>) {
+    loop {
+        let network = &shared.config.network;
+        if let Err(e) = shared.clock.refresh(&shared.rpc_handler, network).await {
+            log::warn!("chain clock refresh failed: {:?}", e);
+        }
+        sleep(shared.config.intervals.clock()).await;
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ClockInfo {
+    #[serde(flatten)]
+    pub state: ClockState,
+    /// seconds since `refreshed_at`
+    pub age: i64,
+    pub registration_open: bool,
+    pub next_registration_layer: i64,
+    /// estimated unix time of `next_registration_layer`
+    pub next_registration_at: i64,
+    pub next_epoch_layer: i64,
+    /// estimated unix time of `next_epoch_layer`
+    pub next_epoch_at: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/clock",
+    tag = "pool",
+    responses(
+        (status = 200, body = ClockResponse),
+        (status = 503, description = "chain clock not refreshed yet", body = ErrorBody),
+    )
+)]
+pub async fn clock_handler(
+    State(shared): State<Arc<Shared>>,
+) -> Result<ApiResponse<ClockInfo>, ApiError> {
+    let state = shared.clock.current()?;
+    let network = &shared.config.network;
+    let next_registration_layer = state.next_registration_layer(network);
+    let next_epoch_layer = state.next_epoch_layer(network);
+    let info = ClockInfo {
+        state,
+        age: state.age(),
+        registration_open: state.registration_open(network),
+        next_registration_layer,
+        next_registration_at: state.estimate(next_registration_layer, network),
+        next_epoch_layer,
+        next_epoch_at: state.estimate(next_epoch_layer, network),
+    };
+    Ok(ApiResponse::new(info, false))
+}
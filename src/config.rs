@@ -0,0 +1,562 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::Context;
+use axum::http::{HeaderValue, Method};
+use serde::{de, Deserialize, Deserializer};
+use toml::{Table, Value};
+
+use crate::{
+    clock::{CLOCK_INTERVAL, LAYERS_PER_EPOCH, LAYER_DURATION, POET_REGISTRATION_OFFSET},
+    events::EVENT_BUFFER,
+    export::EXPORT_BATCH,
+    health::{MAX_LAYER_LAG, PROBE_TIMEOUT},
+    node::NODE_STATUS_INTERVAL,
+    poolstats::{MAX_BATCH_IDS, MAX_PAGE_SIZE},
+    ratelimit::RouteBurst,
+    rpc::{
+        failover::{AGREEMENT_INTERVAL, MAX_LAYER_DIVERGENCE},
+        retry::{BREAKER_COOLDOWN, BREAKER_THRESHOLD, RETRY_ATTEMPTS},
+        ApiVersion, NodeAuth, NodeEndpoint, RPC_TIMEOUT,
+    },
+    serve::{parse_method, parse_origin, Listen},
+    smesher::SMESHER_INTERVAL,
+    sync::{SYNC_BATCH, SYNC_INTERVAL},
+    timeline::MAX_TIMELINE_EPOCHS,
+};
+
+/// prefix of the environment variables overriding the config file,
+/// `POOLSTATS_<SECTION>_<KEY>` sets `key` in `[section]`
+pub const ENV_PREFIX: &str = "POOLSTATS_";
+/// path of the config file when `--config` is absent
+pub const CONFIG_ENV: &str = "POOLSTATS_CONFIG";
+/// api requests running longer than this are answered with a timeout
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SECTIONS: &[&str] = &[
+    "sources",
+    "network",
+    "intervals",
+    "limits",
+    "listen",
+    "auth",
+    "alerts",
+];
+
+/// a setting that was read but cannot be used, `key` is its dotted path in the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(key: &str, message: impl Display) -> Self {
+        Self {
+            key: key.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn missing(key: &str, flag: &str) -> Self {
+        let env = format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_uppercase());
+        Self::new(
+            key,
+            format!("not set, give it in the config file, {} or {}", env, flag),
+        )
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.key, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// a string parsed like the matching command line flag
+fn parsed_opt<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+/// an array, or one comma separated string as environment variables carry lists
+fn strings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Joined(String),
+        Items(Vec<String>),
+    }
+    Ok(match List::deserialize(deserializer)? {
+        List::Joined(joined) => joined
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        List::Items(items) => items,
+    })
+}
+
+fn parsed_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    strings(deserializer)?
+        .iter()
+        .map(|item| item.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+fn origins<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<HeaderValue>, D::Error> {
+    strings(deserializer)?
+        .iter()
+        .map(|item| parse_origin(item).map_err(de::Error::custom))
+        .collect()
+}
+
+fn methods<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Method>, D::Error> {
+    strings(deserializer)?
+        .iter()
+        .map(|item| parse_method(item).map_err(de::Error::custom))
+        .collect()
+}
+
+/// a source a command reads, `Config::validate` requires only these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// `sources.db`, the chain
+    Db,
+    /// `sources.local`, keys and registrations
+    Local,
+    Nodes,
+}
+
+impl Source {
+    pub const ALL: &'static [Source] = &[Source::Db, Source::Local, Source::Nodes];
+}
+
+/// where the data is read from
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sources {
+    /// the node's state.sql
+    pub db: Option<String>,
+    /// the node's local.sql
+    pub local: Option<String>,
+    /// directory of the cache db, the working directory when absent
+    pub datadir: Option<PathBuf>,
+    #[serde(deserialize_with = "parsed_list")]
+    pub nodes: Vec<NodeEndpoint>,
+    pub node_api: ApiVersion,
+    /// pem ca bundle trusted for https and grpcs nodes
+    pub node_ca: Option<PathBuf>,
+}
+
+/// chain parameters, the defaults are mainnet's
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Network {
+    pub layers_per_epoch: i64,
+    /// layer within an epoch where the next poet round starts taking registrations
+    pub poet_registration_offset: i64,
+    pub layer_duration_secs: i64,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            layers_per_epoch: LAYERS_PER_EPOCH,
+            poet_registration_offset: POET_REGISTRATION_OFFSET,
+            layer_duration_secs: LAYER_DURATION,
+        }
+    }
+}
+
+/// pauses between runs of the background loops, in seconds
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Intervals {
+    pub sync_secs: u64,
+    pub clock_secs: u64,
+    pub smesher_secs: u64,
+    pub node_status_secs: u64,
+    /// endpoints are compared for agreement on epoch and layer
+    pub agreement_secs: u64,
+}
+
+impl Default for Intervals {
+    fn default() -> Self {
+        Self {
+            sync_secs: SYNC_INTERVAL.as_secs(),
+            clock_secs: CLOCK_INTERVAL.as_secs(),
+            smesher_secs: SMESHER_INTERVAL.as_secs(),
+            node_status_secs: NODE_STATUS_INTERVAL.as_secs(),
+            agreement_secs: AGREEMENT_INTERVAL.as_secs(),
+        }
+    }
+}
+
+impl Intervals {
+    pub fn sync(&self) -> Duration {
+        Duration::from_secs(self.sync_secs)
+    }
+
+    pub fn clock(&self) -> Duration {
+        Duration::from_secs(self.clock_secs)
+    }
+
+    pub fn smesher(&self) -> Duration {
+        Duration::from_secs(self.smesher_secs)
+    }
+
+    pub fn node_status(&self) -> Duration {
+        Duration::from_secs(self.node_status_secs)
+    }
+
+    pub fn agreement(&self) -> Duration {
+        Duration::from_secs(self.agreement_secs)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// keys read from `post` per query
+    pub sync_batch: i64,
+    pub request_timeout_secs: u64,
+    /// per node request, retries take longer
+    pub rpc_timeout_secs: u64,
+    /// tries per node request, including the first
+    pub rpc_retry_attempts: u32,
+    /// consecutive failed requests that stop requests to a node
+    pub breaker_threshold: u32,
+    /// how long a stopped node is left alone before one request is let through
+    pub breaker_cooldown_secs: u64,
+    /// per dependency checked by `/readyz` and `check`
+    pub probe_timeout_secs: u64,
+    /// requests per second refilled for each client and route
+    pub rate_limit: f64,
+    /// requests a client may burst on a route
+    pub rate_burst: u32,
+    /// `route=burst` overrides, `auth` sets the failed authentications an ip may burst
+    #[serde(deserialize_with = "parsed_list")]
+    pub route_bursts: Vec<RouteBurst>,
+    /// ids in one `/nodes_info/batch` request
+    pub max_batch_ids: usize,
+    /// nodes in one `/nodes_info` page
+    pub max_page_size: i64,
+    /// keys read from `post` per query while exporting
+    pub export_batch: i64,
+    /// epochs in one `/timeline` response
+    pub max_timeline_epochs: i64,
+    /// events buffered per `/events` subscriber before it starts lagging
+    pub event_buffer: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            sync_batch: SYNC_BATCH,
+            request_timeout_secs: REQUEST_TIMEOUT.as_secs(),
+            rpc_timeout_secs: RPC_TIMEOUT.as_secs(),
+            rpc_retry_attempts: RETRY_ATTEMPTS,
+            breaker_threshold: BREAKER_THRESHOLD,
+            breaker_cooldown_secs: BREAKER_COOLDOWN.as_secs(),
+            probe_timeout_secs: PROBE_TIMEOUT.as_secs(),
+            rate_limit: 2.0,
+            rate_burst: 20,
+            route_bursts: Vec::new(),
+            max_batch_ids: MAX_BATCH_IDS,
+            max_page_size: MAX_PAGE_SIZE,
+            export_batch: EXPORT_BATCH,
+            max_timeline_epochs: MAX_TIMELINE_EPOCHS,
+            event_buffer: EVENT_BUFFER,
+        }
+    }
+}
+
+impl Limits {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn rpc_timeout(&self) -> Duration {
+        Duration::from_secs(self.rpc_timeout_secs)
+    }
+
+    pub fn probe_timeout(&self) -> Duration {
+        Duration::from_secs(self.probe_timeout_secs)
+    }
+
+    pub fn breaker_cooldown(&self) -> Duration {
+        Duration::from_secs(self.breaker_cooldown_secs)
+    }
+}
+
+/// where `serve` accepts connections
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenConfig {
+    /// ip:port or unix:/path/to.sock
+    #[serde(deserialize_with = "parsed_opt")]
+    pub address: Option<Listen>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// any when empty
+    #[serde(deserialize_with = "origins")]
+    pub cors_origins: Vec<HeaderValue>,
+    /// any when empty
+    #[serde(deserialize_with = "methods")]
+    pub cors_methods: Vec<Method>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Auth {
+    /// credentials for every node, bearer:<token> or basic:<user>:<password>
+    #[serde(deserialize_with = "parsed_opt")]
+    pub node: Option<NodeAuth>,
+}
+
+/// when readiness and endpoint health start reporting problems
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Alerts {
+    /// a sync pass older than this means the sync loop is stuck or failing,
+    /// twice the sync interval when absent
+    pub max_sync_age_secs: Option<u64>,
+    /// layers the chain db may trail the node's current layer by
+    pub max_layer_lag: i64,
+    /// endpoints trailing the most advanced one by more layers than this are flagged
+    pub max_layer_divergence: i64,
+}
+
+impl Default for Alerts {
+    fn default() -> Self {
+        Self {
+            max_sync_age_secs: None,
+            max_layer_lag: MAX_LAYER_LAG,
+            max_layer_divergence: MAX_LAYER_DIVERGENCE,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub sources: Sources,
+    pub network: Network,
+    pub intervals: Intervals,
+    pub limits: Limits,
+    pub listen: ListenConfig,
+    pub auth: Auth,
+    pub alerts: Alerts,
+}
+
+/// `POOLSTATS_<SECTION>_<KEY>` as `section.key`, none for variables of other programs
+fn env_key(name: &str) -> Option<Result<(&'static str, String), ConfigError>> {
+    let rest = name.strip_prefix(ENV_PREFIX)?;
+    if name == CONFIG_ENV {
+        return None;
+    }
+    let rest = rest.to_lowercase();
+    let section = SECTIONS.iter().find(|section| {
+        rest.strip_prefix(*section)
+            .is_some_and(|key| key.len() > 1 && key.starts_with('_'))
+    });
+    Some(match section {
+        Some(section) => Ok((section, rest[section.len() + 1..].to_string())),
+        None => Err(ConfigError::new(
+            name,
+            format!(
+                "expected {}<SECTION>_<KEY> with a known section",
+                ENV_PREFIX
+            ),
+        )),
+    })
+}
+
+/// numbers and booleans are taken as such, anything else as a string
+fn env_value(value: &str) -> Value {
+    match format!("value = {}", value).parse::<Table>() {
+        Ok(mut table) => match table.remove("value") {
+            Some(value @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_))) => value,
+            _ => Value::String(value.to_string()),
+        },
+        Err(_) => Value::String(value.to_string()),
+    }
+}
+
+impl Config {
+    /// reads `path` if given, then applies the `POOLSTATS_*` variables of `env` over it;
+    /// the result still needs `validate` once command line flags are applied
+    pub fn load(
+        path: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> anyhow::Result<Self> {
+        let mut table = match path {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("reading config {}", path.display()))?
+                .parse::<Table>()
+                .with_context(|| format!("parsing config {}", path.display()))?,
+            None => Table::new(),
+        };
+        for (name, value) in env {
+            let Some(key) = env_key(&name) else {
+                continue;
+            };
+            let (section, key) = key?;
+            let section = table
+                .entry(section)
+                .or_insert_with(|| Value::Table(Table::new()));
+            let Value::Table(section) = section else {
+                return Err(ConfigError::new(&name, "section is not a table").into());
+            };
+            section.insert(key, env_value(&value));
+        }
+        let config = serde_path_to_error::deserialize(Value::Table(table)).map_err(|e| {
+            let key = e.path().to_string();
+            ConfigError::new(&key, e.into_inner().message())
+        })?;
+        Ok(config)
+    }
+
+    /// twice the sync interval unless configured
+    pub fn max_sync_age(&self) -> Duration {
+        match self.alerts.max_sync_age_secs {
+            Some(secs) => Duration::from_secs(secs),
+            None => 2 * self.intervals.sync(),
+        }
+    }
+
+    pub fn datadir(&self) -> PathBuf {
+        self.sources
+            .datadir
+            .clone()
+            .unwrap_or_else(|| std::env::current_dir().unwrap())
+    }
+
+    /// the sources a command reads and every range, `serve` checks its own
+    pub fn validate(&self, needs: &[Source]) -> Result<(), ConfigError> {
+        if needs.contains(&Source::Db) && self.sources.db.is_none() {
+            return Err(ConfigError::missing("sources.db", "--db"));
+        }
+        if needs.contains(&Source::Local) && self.sources.local.is_none() {
+            return Err(ConfigError::missing("sources.local", "--local"));
+        }
+        if needs.contains(&Source::Nodes) && self.sources.nodes.is_empty() {
+            return Err(ConfigError::missing("sources.nodes", "--node"));
+        }
+        let network = &self.network;
+        if network.layers_per_epoch < 1 {
+            return Err(ConfigError::new(
+                "network.layers_per_epoch",
+                "must be at least 1",
+            ));
+        }
+        if !(0..network.layers_per_epoch).contains(&network.poet_registration_offset) {
+            return Err(ConfigError::new(
+                "network.poet_registration_offset",
+                format!(
+                    "must be a layer within the epoch, 0 to {}",
+                    network.layers_per_epoch - 1
+                ),
+            ));
+        }
+        let positive = [
+            ("network.layer_duration_secs", network.layer_duration_secs),
+            ("intervals.sync_secs", self.intervals.sync_secs as i64),
+            ("intervals.clock_secs", self.intervals.clock_secs as i64),
+            ("intervals.smesher_secs", self.intervals.smesher_secs as i64),
+            (
+                "intervals.node_status_secs",
+                self.intervals.node_status_secs as i64,
+            ),
+            (
+                "intervals.agreement_secs",
+                self.intervals.agreement_secs as i64,
+            ),
+            ("limits.sync_batch", self.limits.sync_batch),
+            (
+                "limits.request_timeout_secs",
+                self.limits.request_timeout_secs as i64,
+            ),
+            (
+                "limits.rpc_timeout_secs",
+                self.limits.rpc_timeout_secs as i64,
+            ),
+            (
+                "limits.probe_timeout_secs",
+                self.limits.probe_timeout_secs as i64,
+            ),
+            (
+                "limits.rpc_retry_attempts",
+                self.limits.rpc_retry_attempts as i64,
+            ),
+            (
+                "limits.breaker_threshold",
+                self.limits.breaker_threshold as i64,
+            ),
+            (
+                "limits.breaker_cooldown_secs",
+                self.limits.breaker_cooldown_secs as i64,
+            ),
+            ("limits.rate_burst", self.limits.rate_burst as i64),
+            ("limits.max_batch_ids", self.limits.max_batch_ids as i64),
+            ("limits.max_page_size", self.limits.max_page_size),
+            ("limits.export_batch", self.limits.export_batch),
+            (
+                "limits.max_timeline_epochs",
+                self.limits.max_timeline_epochs,
+            ),
+            ("limits.event_buffer", self.limits.event_buffer as i64),
+        ];
+        if let Some((key, _)) = positive.iter().find(|(_, value)| *value < 1) {
+            return Err(ConfigError::new(key, "must be at least 1"));
+        }
+        if !(self.limits.rate_limit > 0.0 && self.limits.rate_limit.is_finite()) {
+            return Err(ConfigError::new("limits.rate_limit", "must be positive"));
+        }
+        if self.max_sync_age() <= self.intervals.sync() {
+            return Err(ConfigError::new(
+                "alerts.max_sync_age_secs",
+                format!(
+                    "must be longer than intervals.sync_secs ({})",
+                    self.intervals.sync_secs
+                ),
+            ));
+        }
+        if self.alerts.max_layer_lag < 0 {
+            return Err(ConfigError::new(
+                "alerts.max_layer_lag",
+                "must not be negative",
+            ));
+        }
+        if self.alerts.max_layer_divergence < 0 {
+            return Err(ConfigError::new(
+                "alerts.max_layer_divergence",
+                "must not be negative",
+            ));
+        }
+        match (&self.listen.tls_cert, &self.listen.tls_key) {
+            (Some(_), None) => Err(ConfigError::missing("listen.tls_key", "--tls-key")),
+            (None, Some(_)) => Err(ConfigError::missing("listen.tls_cert", "--tls-cert")),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::error::ApiError;
+
+/// assets compiled into the binary, name and content type
+const ASSETS: &[(&str, &str, &str)] = &[
+    (
+        "index.html",
+        "text/html; charset=utf-8",
+        include_str!("../assets/dashboard/index.html"),
+    ),
+    (
+        "app.js",
+        "text/javascript; charset=utf-8",
+        include_str!("../assets/dashboard/app.js"),
+    ),
+    (
+        "style.css",
+        "text/css; charset=utf-8",
+        include_str!("../assets/dashboard/style.css"),
+    ),
+];
+
+fn asset(name: &str) -> Response {
+    match ASSETS.iter().find(|(asset, _, _)| *asset == name) {
+        Some((_, content_type, body)) => (
+            [
+                (header::CONTENT_TYPE, *content_type),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            *body,
+        )
+            .into_response(),
+        None => ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("no dashboard asset {}", name),
+        )
+        .into_response(),
+    }
+}
+
+/// asset urls are relative, so the page has to be served from the directory
+pub async fn redirect_handler() -> Redirect {
+    Redirect::permanent("/dashboard/")
+}
+
+pub async fn index_handler() -> Response {
+    asset("index.html")
+}
+
+pub async fn asset_handler(Path(name): Path<String>) -> Response {
+    asset(&name)
+}
@@ -0,0 +1,133 @@
+use std::fmt::Display;
+
+use axum::{
+    extract::rejection::{JsonRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// machine readable, stable across releases
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Display) -> Self {
+        Self {
+            status,
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Display) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn database(e: impl Display) -> Self {
+        log::error!("database error: {}", e);
+        Self::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "database_unavailable",
+            "database unavailable",
+        )
+    }
+
+    pub fn rpc(e: impl Display) -> Self {
+        log::error!("rpc error: {}", e);
+        Self::new(
+            StatusCode::BAD_GATEWAY,
+            "rpc_unavailable",
+            "node rpc unavailable",
+        )
+    }
+
+    pub fn timeout() -> Self {
+        Self::new(StatusCode::REQUEST_TIMEOUT, "timeout", "request timed out")
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::database(e)
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(e: JsonRejection) -> Self {
+        Self::new(e.status(), "invalid_request", e.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(e: QueryRejection) -> Self {
+        Self::new(e.status(), "invalid_query", e.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body =
+            json!({"code": self.status.as_u16(), "error": self.code, "message": self.message});
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// success envelope, `partial` is set when some of the data could not be loaded
+#[derive(Debug)]
+pub struct ApiResponse<T> {
+    pub data: T,
+    pub partial: bool,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn new(data: T, partial: bool) -> Self {
+        Self { data, partial }
+    }
+}
+
+impl<T: Serialize> IntoResponse for ApiResponse<T> {
+    fn into_response(self) -> Response {
+        Json(json!({"code": 200, "data": self.data, "partial": self.partial})).into_response()
+    }
+}
+
+/// collects the outcome of independent queries feeding one response
+#[derive(Debug, Default)]
+pub struct Partial {
+    checks: usize,
+    failures: usize,
+}
+
+impl Partial {
+    pub fn check<T: Default, E: Display>(&mut self, result: Result<T, E>) -> T {
+        self.checks += 1;
+        result.unwrap_or_else(|e| {
+            log::warn!("partial response: {}", e);
+            self.failures += 1;
+            T::default()
+        })
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.failures > 0
+    }
+
+    pub fn all_failed(&self) -> bool {
+        self.checks > 0 && self.failures == self.checks
+    }
+}
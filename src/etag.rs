@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{error::ApiError, metrics::TrackDbError, DBHandler, Shared};
+
+/// largest request body hashed into an etag, same as the json extractor's default
+pub const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// what a tagged route depends on besides the cache and the request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Depends {
+    /// the current epoch of the clock
+    pub epoch: bool,
+    /// keys read live from the node's `post` table
+    pub post: bool,
+    /// registrations read live from the node's `poet_registration` table, for the round picked
+    /// by the registration window of the clock
+    pub registrations: bool,
+    /// sync state and top layer of the node
+    pub node: bool,
+}
+
+impl DBHandler {
+    /// counts writes to the cache, by sync passes, smesher refreshes and backfills
+    pub async fn generation(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT generation FROM cache_generation WHERE id = 1")
+            .fetch_one(&self.poolstats)
+            .await
+            .track("poolstats")
+    }
+
+    pub async fn bump_generation(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE cache_generation SET generation = generation + 1 WHERE id = 1")
+            .execute(&self.poolstats)
+            .await
+            .track("poolstats")?;
+        Ok(())
+    }
+
+    /// count and largest id of the initialized keys, which change when a key is added or removed
+    pub async fn post_version(&self) -> Result<(i64, Option<Vec<u8>>), sqlx::Error> {
+        sqlx::query_as("SELECT COUNT (*), MAX (id) FROM post")
+            .fetch_one(&self.local)
+            .await
+            .track("local")
+    }
+
+    /// count and latest round end of the node's registrations, which change when it registers
+    /// for a round or drops an old one
+    pub async fn registration_version(&self) -> Result<(i64, Option<i64>), sqlx::Error> {
+        sqlx::query_as("SELECT COUNT (*), MAX (round_end) FROM poet_registration")
+            .fetch_one(&self.local)
+            .await
+            .track("local")
+    }
+}
+
+fn push_version(versions: &mut Vec<u8>, version: Option<i64>) {
+    versions.extend(version.unwrap_or(-1).to_be_bytes());
+}
+
+/// weak tag of everything a synced response depends on: the cache `generation`, the `versions`
+/// of data refreshed outside the cache and the request
+fn etag(generation: i64, versions: &[u8], req: &Request, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(versions);
+    hasher.update(req.method().as_str());
+    hasher.update(b" ");
+    hasher.update(req.uri().to_string());
+    hasher.update(b"\n");
+    hasher.update(body);
+    let digest = hex::encode(hasher.finalize());
+    format!("W/\"{}-{}\"", generation, &digest[..16])
+}
+
+/// weak comparison, any listed tag or `*` matches
+fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// tags responses that only change with the cache and what `depends` lists, answers a matching
+/// `If-None-Match` with 304
+///
+/// post lookups are tagged too since their body is part of the tag, which lets pollers of
+/// `/nodes_info` skip unchanged pages
+pub async fn conditional(
+    State((shared, depends)): State<(Arc<Shared>, Depends)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let generation = match shared.db_handler.generation().await {
+        Ok(generation) if generation > 0 => generation,
+        // nothing stored yet or the cache db is down, the handler reports the latter
+        _ => return next.run(req).await,
+    };
+    let (parts, body) = req.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            return ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", e)
+                .into_response()
+        }
+    };
+    let req = Request::from_parts(parts, Body::from(body.clone()));
+    let mut versions = vec![];
+    if depends.epoch {
+        push_version(&mut versions, shared.clock.get().map(|clock| clock.epoch));
+    }
+    if depends.post {
+        // keys are initialized on the node, not by a sync pass
+        let Ok((count, max_id)) = shared.db_handler.post_version().await else {
+            return next.run(req).await;
+        };
+        push_version(&mut versions, Some(count));
+        versions.extend(max_id.unwrap_or_default());
+    }
+    if depends.registrations {
+        // the round switches mid-epoch, when the window opens
+        let network = &shared.config.network;
+        push_version(
+            &mut versions,
+            shared
+                .clock
+                .get()
+                .map(|clock| clock.registration_open(network) as i64),
+        );
+        let Ok((count, round_end)) = shared.db_handler.registration_version().await else {
+            return next.run(req).await;
+        };
+        push_version(&mut versions, Some(count));
+        push_version(&mut versions, round_end);
+    }
+    if depends.node {
+        // peers come and go all the time, only a change of sync state or a new layer counts
+        let node = shared.node.get();
+        push_version(
+            &mut versions,
+            node.as_ref().map(|node| node.state.synced as i64),
+        );
+        push_version(
+            &mut versions,
+            node.as_ref().map(|node| node.state.top_layer),
+        );
+    }
+    let etag = etag(generation, &versions, &req, &body);
+    let Ok(value) = HeaderValue::from_str(&etag) else {
+        return next.run(req).await;
+    };
+    let mut resp = if matches(req.headers(), &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        next.run(req).await
+    };
+    if matches!(resp.status(), StatusCode::OK | StatusCode::NOT_MODIFIED) {
+        let headers = resp.headers_mut();
+        headers.insert(header::ETAG, value);
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    }
+    resp
+}
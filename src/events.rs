@@ -0,0 +1,139 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{error::ApiError, poolstats::AtxInfo, Shared};
+
+/// events buffered per subscriber before it starts lagging, default of `limits.event_buffer`
+pub const EVENT_BUFFER: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum PoolEvent {
+    AtxSaved {
+        id: String,
+        num_units: i64,
+        atx: AtxInfo,
+    },
+    PoetRegistrationSaved {
+        id: String,
+        num_units: i64,
+        round_id: String,
+    },
+    EpochRollover {
+        epoch: i64,
+    },
+    RegistrationWindowOpened {
+        round_id: String,
+        layer: i64,
+    },
+    /// registered to the round but no atx found for the epoch, once per key and epoch
+    AtxMissing {
+        id: String,
+        epoch: i64,
+    },
+    SyncCompleted {
+        epoch: i64,
+        layer: i64,
+        keys: i64,
+        duration_ms: u64,
+    },
+}
+
+impl PoolEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PoolEvent::AtxSaved { .. } => "atx_saved",
+            PoolEvent::PoetRegistrationSaved { .. } => "poet_registration_saved",
+            PoolEvent::EpochRollover { .. } => "epoch_rollover",
+            PoolEvent::RegistrationWindowOpened { .. } => "registration_window_opened",
+            PoolEvent::AtxMissing { .. } => "atx_missing",
+            PoolEvent::SyncCompleted { .. } => "sync_completed",
+        }
+    }
+}
+
+pub fn channel(capacity: usize) -> broadcast::Sender<PoolEvent> {
+    broadcast::channel(capacity).0
+}
+
+impl Shared {
+    /// dropped silently when nobody is subscribed
+    pub fn publish(&self, event: PoolEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventsQuery {
+    /// comma separated event names, every event when absent
+    pub types: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "nodes",
+    params(EventsQuery),
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "server-sent events named after their type", content_type = "text/event-stream", body = PoolEvent),
+        (status = 400, description = "invalid query", body = ErrorBody),
+        (status = 501, description = "served without `--sync`, nothing publishes events", body = ErrorBody),
+    )
+)]
+pub async fn events_handler(
+    State(shared): State<Arc<Shared>>,
+    query: Result<Query<EventsQuery>, QueryRejection>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let Query(query) = query?;
+    let types: Option<Vec<String>> = query
+        .types
+        .map(|types| types.split(',').map(|t| t.trim().to_string()).collect());
+    let receiver = shared.events.subscribe();
+    let stream = stream::unfold((receiver, types), |(mut receiver, types)| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if types
+                        .as_ref()
+                        .is_some_and(|types| !types.iter().any(|t| t == event.name()))
+                    {
+                        continue;
+                    }
+                    let sse = Event::default()
+                        .event(event.name())
+                        .json_data(&event)
+                        .unwrap_or_else(|_| Event::default().comment("unserializable event"));
+                    return Some((Ok(sse), (receiver, types)));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    let sse =
+                        Event::default().comment(format!("lagged, {} events skipped", skipped));
+                    return Some((Ok(sse), (receiver, types)));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// `/events` of a `serve` without `--sync`: events are published by the sync loop of the same
+/// process, so a separate `sync` process cannot reach these subscribers
+pub async fn events_unavailable_handler() -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_IMPLEMENTED,
+        "events_unavailable",
+        "events are only published when the server syncs too, start it with serve --sync",
+    )
+}
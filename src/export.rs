@@ -0,0 +1,226 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{rejection::QueryRejection, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{error::ApiError, poolstats::Key, Shared};
+
+/// keys read from `post` per query while exporting, default of `limits.export_batch`
+pub const EXPORT_BATCH: i64 = 500;
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// leading line before the first row
+    pub fn header(&self) -> Option<String> {
+        match self {
+            ExportFormat::Csv => Some(format!("{}\n", ExportRow::CSV_HEADER)),
+            ExportFormat::Ndjson => None,
+        }
+    }
+
+    pub fn line(&self, row: &ExportRow) -> String {
+        match self {
+            ExportFormat::Csv => format!("{}\n", row.to_csv()),
+            ExportFormat::Ndjson => format!("{}\n", serde_json::to_string(row).unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportRow {
+    pub id: String,
+    pub num_units: i64,
+    pub round_id: Option<String>,
+    pub poet_address: Option<String>,
+    pub atx_id: Option<String>,
+    pub effective_num_units: Option<i64>,
+    pub coinbase: Option<String>,
+}
+
+impl ExportRow {
+    pub const CSV_HEADER: &'static str =
+        "id,num_units,round_id,poet_address,atx_id,effective_num_units,coinbase";
+
+    pub fn to_csv(&self) -> String {
+        [
+            self.id.clone(),
+            self.num_units.to_string(),
+            self.round_id.clone().unwrap_or_default(),
+            self.poet_address.clone().unwrap_or_default(),
+            self.atx_id.clone().unwrap_or_default(),
+            self.effective_num_units
+                .map(|units| units.to_string())
+                .unwrap_or_default(),
+            self.coinbase.clone().unwrap_or_default(),
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+struct ExportState {
+    shared: Arc<Shared>,
+    epoch: i64,
+    cursor: Option<String>,
+    keys: VecDeque<Key>,
+    done: bool,
+}
+
+impl ExportState {
+    async fn row(&self, key: Key) -> Result<ExportRow, sqlx::Error> {
+        let db = &self.shared.db_handler;
+        let registeration = db
+            .get_chain_registerations_by_id(key.id.clone(), self.epoch.to_string())
+            .await?
+            .into_iter()
+            .next();
+        let atx = match db.get_atxs_by_id(key.id.clone(), self.epoch).await {
+            Ok(atx) => Some(atx),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(ExportRow {
+            id: key.id,
+            num_units: key.num_units,
+            round_id: registeration.as_ref().map(|r| r.round_id.clone()),
+            poet_address: registeration.map(|r| r.address),
+            atx_id: atx.as_ref().map(|atx| atx.atx_id.clone()),
+            effective_num_units: atx.as_ref().map(|atx| atx.effective_num_units),
+            coinbase: atx.map(|atx| atx.coinbase),
+        })
+    }
+}
+
+/// every initialized key with its registration and atx for `epoch`, read page by page
+pub fn export_rows(
+    shared: Arc<Shared>,
+    epoch: i64,
+) -> impl Stream<Item = Result<ExportRow, sqlx::Error>> {
+    let state = ExportState {
+        shared,
+        epoch,
+        cursor: None,
+        keys: VecDeque::new(),
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        if state.keys.is_empty() {
+            if state.done {
+                return None;
+            }
+            let batch = state.shared.config.limits.export_batch;
+            match state
+                .shared
+                .db_handler
+                .get_init_keys(batch, state.cursor.clone())
+                .await
+            {
+                Ok(keys) => {
+                    state.cursor = keys.last().map(|key| key.id.clone());
+                    state.done = (keys.len() as i64) < batch;
+                    state.keys = keys.into();
+                }
+                Err(e) => {
+                    state.done = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }
+        let key = state.keys.pop_front()?;
+        let row = state.row(key).await;
+        Some((row, state))
+    })
+}
+
+/// rendered lines, header first
+pub fn export_lines(
+    shared: Arc<Shared>,
+    epoch: i64,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<String, sqlx::Error>> {
+    let header = stream::iter(format.header().map(Ok));
+    let rows = export_rows(shared, epoch).map(move |row| row.map(|row| format.line(&row)));
+    header.chain(rows)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// defaults to the epoch reported as current by `/overview`
+    pub epoch: Option<i64>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/nodes",
+    tag = "nodes",
+    params(ExportQuery),
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "one row per initialized key", content(("text/csv" = String), ("application/x-ndjson" = String))),
+        (status = 304, description = "unchanged since the etag in If-None-Match"),
+        (status = 400, description = "invalid query", body = ErrorBody),
+        (status = 503, description = "chain clock not refreshed yet", body = ErrorBody),
+    )
+)]
+pub async fn export_handler(
+    State(shared): State<Arc<Shared>>,
+    query: Result<Query<ExportQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let epoch = match query.epoch {
+        Some(epoch) => epoch,
+        None => shared.clock.current()?.epoch - 1,
+    };
+    let extension = match query.format {
+        ExportFormat::Csv => "csv",
+        ExportFormat::Ndjson => "ndjson",
+    };
+    let body = Body::from_stream(export_lines(shared, epoch, query.format));
+    let headers = [
+        (
+            header::CONTENT_TYPE,
+            query.format.content_type().to_string(),
+        ),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"nodes-{}.{}\"", epoch, extension),
+        ),
+    ];
+    Ok((headers, body).into_response())
+}
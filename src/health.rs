@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, fmt::Display, future::Future, sync::Arc, time::Duration};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    clock::{unix_now, ClockState},
+    error::{ApiError, ApiResponse},
+    metrics::TrackDbError,
+    node::NodeStatusInfo,
+    Shared,
+};
+
+/// layers the chain db may trail the node's current layer by, default of `alerts.max_layer_lag`
+pub const MAX_LAYER_LAG: i64 = 10;
+/// upper bound for a single dependency check, default of `limits.probe_timeout_secs`
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// refresh intervals the cached clock or node status may miss before readiness fails
+pub const MISSED_REFRESHES: i64 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Ok,
+    /// works, but what it provides is not reliable, does not fail readiness
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    pub status: ComponentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// numbers the check was decided on, e.g. `age` or `lag`
+    #[serde(flatten)]
+    pub details: BTreeMap<String, i64>,
+}
+
+impl Component {
+    pub fn ok() -> Self {
+        Self {
+            status: ComponentStatus::Ok,
+            message: None,
+            details: BTreeMap::new(),
+        }
+    }
+
+    pub fn error(message: impl Display) -> Self {
+        Self {
+            status: ComponentStatus::Error,
+            message: Some(message.to_string()),
+            details: BTreeMap::new(),
+        }
+    }
+
+    pub fn warning(message: impl Display) -> Self {
+        Self {
+            status: ComponentStatus::Warning,
+            message: Some(message.to_string()),
+            details: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: i64) -> Self {
+        self.details.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status != ComponentStatus::Error
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Health {
+    pub status: ComponentStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Readiness {
+    pub ready: bool,
+    /// some component is degraded, see its message
+    pub warning: bool,
+    pub components: BTreeMap<String, Component>,
+}
+
+/// the process is up and serving, dependencies are not consulted
+pub async fn healthz_handler() -> Result<ApiResponse<Health>, ApiError> {
+    Ok(ApiResponse::new(
+        Health {
+            status: ComponentStatus::Ok,
+        },
+        false,
+    ))
+}
+
+async fn probe<T, E: Display>(
+    timeout: Duration,
+    check: impl Future<Output = Result<T, E>>,
+) -> Result<T, String> {
+    match tokio::time::timeout(timeout, check).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+    }
+}
+
+async fn check_pool(pool: &Pool<Sqlite>, name: &str, timeout: Duration) -> Component {
+    let query = async {
+        sqlx::query_scalar::<_, i64>("SELECT 1")
+            .fetch_one(pool)
+            .await
+            .track(name)
+    };
+    match probe(timeout, query).await {
+        Ok(_) => Component::ok(),
+        Err(e) => Component::error(e),
+    }
+}
+
+async fn check_sync(shared: &Shared) -> Component {
+    let timeout = shared.config.limits.probe_timeout();
+    match probe(timeout, shared.db_handler.last_sync_pass()).await {
+        Ok(Some(pass)) => {
+            let age = pass.age();
+            let component = if age > shared.config.max_sync_age().as_secs() as i64 {
+                Component::error(format!("last sync pass finished {}s ago", age))
+            } else {
+                Component::ok()
+            };
+            component.with("pass", pass.id).with("age", age)
+        }
+        Ok(None) => Component::error("no sync pass completed yet"),
+        Err(e) => Component::error(e),
+    }
+}
+
+/// `component` unless the cached answer it was decided on missed `MISSED_REFRESHES` refreshes
+fn check_age(component: Component, name: &str, age: i64, interval: Duration) -> Component {
+    let max_age = MISSED_REFRESHES * interval.as_secs().max(1) as i64;
+    let component = if age > max_age {
+        Component::error(format!("{} last refreshed {}s ago", name, age))
+    } else {
+        component
+    };
+    component.with("age", age)
+}
+
+pub fn check_clock(clock: Option<ClockState>, interval: Duration) -> Component {
+    let Some(clock) = clock else {
+        return Component::error("chain clock not refreshed yet");
+    };
+    let component = Component::ok().with("layer", clock.layer);
+    check_age(component, "chain clock", clock.age(), interval)
+}
+
+pub fn check_node(status: Option<NodeStatusInfo>, interval: Duration) -> Component {
+    let Some(NodeStatusInfo {
+        state,
+        refreshed_at,
+    }) = status
+    else {
+        return Component::error("node status not refreshed yet");
+    };
+    let component = if state.synced {
+        Component::ok()
+    } else {
+        Component::warning("node is not synced, registration and atx counts may be incomplete")
+    };
+    let component = component
+        .with("connected_peers", state.connected_peers)
+        .with("synced_layer", state.synced_layer)
+        .with("top_layer", state.top_layer)
+        .with("verified_layer", state.verified_layer);
+    let age = (unix_now() - refreshed_at).max(0);
+    check_age(component, "node status", age, interval)
+}
+
+fn check_chain_lag(
+    node_layer: Option<i64>,
+    chain_layer: Result<Option<i64>, String>,
+    max_lag: i64,
+) -> Component {
+    let chain_layer = match chain_layer {
+        Ok(Some(layer)) => layer,
+        Ok(None) => return Component::error("chain db holds no layers"),
+        Err(e) => return Component::error(e),
+    };
+    let Some(node_layer) = node_layer else {
+        return Component::error("node layer unknown").with("chain_layer", chain_layer);
+    };
+    let lag = (node_layer - chain_layer).max(0);
+    let component = if lag > max_lag {
+        Component::error(format!("chain db is {} layers behind the node", lag))
+    } else {
+        Component::ok()
+    };
+    component
+        .with("chain_layer", chain_layer)
+        .with("node_layer", node_layer)
+        .with("lag", lag)
+}
+
+/// tables and columns the queries rely on, a node upgrade may rename them
+const CHAIN_SCHEMA: &[(&str, &[&str])] = &[
+    (
+        "atxs",
+        &["id", "pubkey", "epoch", "effective_num_units", "coinbase"],
+    ),
+    ("layers", &["id"]),
+];
+const LOCAL_SCHEMA: &[(&str, &[&str])] = &[
+    ("post", &["id", "num_units"]),
+    (
+        "poet_registration",
+        &["id", "address", "round_id", "round_end"],
+    ),
+];
+
+async fn missing_columns(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    schema: &[(&str, &[&str])],
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut missing = Vec::new();
+    for (table, columns) in schema {
+        let present: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info($1)")
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .track(name)?;
+        if present.is_empty() {
+            missing.push(table.to_string());
+            continue;
+        }
+        missing.extend(
+            columns
+                .iter()
+                .filter(|column| !present.iter().any(|name| name == *column))
+                .map(|column| format!("{}.{}", table, column)),
+        );
+    }
+    Ok(missing)
+}
+
+async fn check_schema(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    schema: &[(&str, &[&str])],
+    timeout: Duration,
+) -> Component {
+    match probe(timeout, missing_columns(pool, name, schema)).await {
+        Ok(missing) if missing.is_empty() => Component::ok(),
+        Ok(missing) => Component::error(format!("missing {}", missing.join(", "))),
+        Err(e) => Component::error(e),
+    }
+}
+
+/// migrations of the cache db not applied yet, `serve` and `sync` apply them on start
+async fn check_migrations(pool: &Pool<Sqlite>, timeout: Duration) -> Component {
+    let migrator = sqlx::migrate!();
+    let applied = async {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(pool)
+        .await
+        .track("poolstats")?;
+        if !exists {
+            return Ok(Vec::new());
+        }
+        sqlx::query_scalar::<_, i64>("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await
+            .track("poolstats")
+    };
+    let applied = match probe(timeout, applied).await {
+        Ok(applied) => applied,
+        Err(e) => return Component::error(e),
+    };
+    let pending = migrator
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .count() as i64;
+    let component = if pending > 0 {
+        Component::warning(format!("{} migrations pending", pending))
+    } else {
+        Component::ok()
+    };
+    component.with("pending", pending)
+}
+
+/// readiness plus the schemas of the chain and local dbs and pending cache migrations, refreshes
+/// the clock and node status first since no refresh loop runs
+pub async fn check(shared: &Shared) -> BTreeMap<String, Component> {
+    let db = &shared.db_handler;
+    let timeout = shared.config.limits.probe_timeout();
+    let rpc = &shared.rpc_handler;
+    let (clock, node) = tokio::join!(
+        probe(timeout, shared.clock.refresh(rpc, &shared.config.network)),
+        probe(timeout, shared.node.refresh(rpc)),
+    );
+    let (readiness, chain_schema, local_schema, migrations) = tokio::join!(
+        readiness(shared),
+        check_schema(&db.chain, "chain", CHAIN_SCHEMA, timeout),
+        check_schema(&db.local, "local", LOCAL_SCHEMA, timeout),
+        check_migrations(&db.poolstats, timeout),
+    );
+    let mut components = readiness.components;
+    if let Err(e) = clock {
+        components.insert("rpc".to_string(), Component::error(e));
+    }
+    if let Err(e) = node {
+        components.insert("node".to_string(), Component::error(e));
+    }
+    components.insert("chain_schema".to_string(), chain_schema);
+    components.insert("local_schema".to_string(), local_schema);
+    components.insert("migrations".to_string(), migrations);
+    components
+}
+
+/// checks every dependency, 503 with the same body when any of them fails; the node is judged by
+/// the clock and status its refresh loops cached, so a probe sends no rpc
+pub async fn readyz_handler(State(shared): State<Arc<Shared>>) -> Response {
+    let readiness = readiness(&shared).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({"code": status.as_u16(), "data": readiness})),
+    )
+        .into_response()
+}
+
+pub async fn readiness(shared: &Shared) -> Readiness {
+    let db = &shared.db_handler;
+    let timeout = shared.config.limits.probe_timeout();
+    let intervals = &shared.config.intervals;
+    let (chain, local, poolstats, sync, chain_layer) = tokio::join!(
+        check_pool(&db.chain, "chain", timeout),
+        check_pool(&db.local, "local", timeout),
+        check_pool(&db.poolstats, "poolstats", timeout),
+        check_sync(shared),
+        probe(timeout, db.latest_chain_layer()),
+    );
+    let clock = shared.clock.get();
+    let node_layer = clock.map(|clock| clock.layer);
+    let components: BTreeMap<String, Component> = [
+        ("chain", chain),
+        ("local", local),
+        ("poolstats", poolstats),
+        ("rpc", check_clock(clock, intervals.clock())),
+        (
+            "node",
+            check_node(shared.node.get(), intervals.node_status()),
+        ),
+        ("sync", sync),
+        (
+            "chain_lag",
+            check_chain_lag(node_layer, chain_layer, shared.config.alerts.max_layer_lag),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, component)| (name.to_string(), component))
+    .collect();
+    let ready = components.values().all(Component::is_ok);
+    let warning = components
+        .values()
+        .any(|component| component.status == ComponentStatus::Warning);
+    Readiness {
+        ready,
+        warning,
+        components,
+    }
+}
@@ -1,11 +1,33 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
+pub mod auth;
 pub mod chain;
+pub mod clock;
+pub mod config;
+pub mod dashboard;
+pub mod error;
+pub mod etag;
+pub mod events;
+pub mod export;
+pub mod health;
+pub mod metrics;
+pub mod node;
+pub mod openapi;
 pub mod poolstats;
+pub mod ratelimit;
 pub mod rpc;
+pub mod serve;
+pub mod smesher;
+pub mod sync;
+pub mod timeline;
 
+use clock::ChainClock;
+use config::Config;
+use events::PoolEvent;
+use node::NodeMonitor;
 use rpc::RpcHandler;
-use sqlx::{Pool, Sqlite};
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+use tokio::sync::broadcast;
 
 pub struct DBHandler {
     pub chain: Pool<Sqlite>,
@@ -21,18 +43,49 @@ impl DBHandler {
             poolstats,
         }
     }
+
+    /// the node's local db, connected lazily; with `cache` attached as `cache` node lists are
+    /// filtered by what the cache holds in one query
+    pub fn connect_local(url: &str, cache: Option<&Path>) -> Result<Pool<Sqlite>, sqlx::Error> {
+        let options = SqlitePoolOptions::new();
+        let Some(cache) = cache else {
+            return options.connect_lazy(url);
+        };
+        let cache = cache.to_string_lossy().into_owned();
+        options
+            .after_connect(move |conn, _| {
+                let cache = cache.clone();
+                Box::pin(async move {
+                    sqlx::query("ATTACH DATABASE $1 AS cache")
+                        .bind(cache)
+                        .execute(conn)
+                        .await?;
+                    Ok(())
+                })
+            })
+            .connect_lazy(url)
+    }
 }
 
 pub struct Shared {
     pub db_handler: DBHandler,
     pub rpc_handler: RpcHandler,
+    pub config: Config,
+    pub clock: ChainClock,
+    pub node: NodeMonitor,
+    pub events: broadcast::Sender<PoolEvent>,
 }
 
 impl Shared {
-    pub fn new(db_handler: DBHandler, rpc_handler: RpcHandler) -> Arc<Self> {
+    pub fn new(db_handler: DBHandler, rpc_handler: RpcHandler, config: Config) -> Arc<Self> {
+        let events = events::channel(config.limits.event_buffer);
         Arc::new(Self {
             db_handler,
             rpc_handler,
+            config,
+            clock: ChainClock::default(),
+            node: NodeMonitor::default(),
+            events,
         })
     }
 }
@@ -1,213 +1,672 @@
-use std::{cmp, ops::Range, path::PathBuf, time::Duration};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::Arc,
+    time::Duration,
+};
 
+use anyhow::{bail, Context};
 use axum::{
     error_handling::HandleErrorLayer,
-    http::StatusCode,
-    routing::{get, post},
+    http::{HeaderValue, Method},
+    middleware,
+    routing::{delete, get, post},
     BoxError, Router,
 };
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use futures::StreamExt;
 use log::info;
 use poolstats::{
-    poolstats::{get_nodes_info, overview_handler, Key},
-    rpc::RpcHandler,
+    auth::{create_key_handler, list_keys_handler, require_scope, revoke_key_handler, Scope},
+    clock::{self, clock_handler},
+    config::{Config, ConfigError, Source, CONFIG_ENV},
+    dashboard,
+    error::ApiError,
+    etag::{conditional, Depends},
+    events::{events_handler, events_unavailable_handler},
+    export::{export_handler, export_lines, ExportFormat},
+    health::{self, healthz_handler, readyz_handler, Component, ComponentStatus},
+    metrics::metrics_handler,
+    node,
+    openapi::{ApiDoc, API_PREFIX},
+    poolstats::{
+        get_nodes_info, get_nodes_info_batch, get_overview, overview_handler, Item, Overview,
+    },
+    ratelimit::{limit_auth_failures, parse_rate, rate_limit, RateLimiter, RouteBurst},
+    rpc::{ApiVersion, NodeAuth, NodeEndpoint, RpcHandler, RpcOptions},
+    serve::{cors_layer, parse_method, parse_origin, serve, tls_acceptor, Listen, ReloadingCert},
+    smesher,
+    sync::{self, sync_status_handler, SyncState},
+    timeline::timeline_handler,
     DBHandler, Shared,
 };
 use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
-use tokio::{net::TcpListener, time::sleep};
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, AsyncWriteExt},
+};
 use tower::{timeout::TimeoutLayer, ServiceBuilder};
-use tower_http::cors::{Any, CorsLayer};
-
-fn get_default_db_path() -> PathBuf {
-    std::env::current_dir().unwrap()
-}
-
-fn get_range(input: Range<i64>, batch: i64) -> Vec<Range<i64>> {
-    let end = input.end;
-    let mut result = vec![];
-    for group in input.step_by(batch as usize) {
-        let start = group;
-        let end = cmp::min(start + batch - 1, end);
-        result.push(start..end)
-    }
-    result
-}
+use tower_http::compression::CompressionLayer;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
+/// flags override the config file and `POOLSTATS_*` environment variables
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Cli {
+    /// toml config file, POOLSTATS_CONFIG when absent
+    #[arg(short, long)]
+    config: Option<PathBuf>,
     /// sqlite db path
     #[arg(short, long)]
-    db: String,
+    db: Option<String>,
     /// sqlite local db path
     #[arg(short, long)]
-    local: String,
-    /// ip:port to listen for api request
-    #[arg(short, long)]
-    listen: String,
-    /// datadir for cache db
-    #[arg(short, long, default_value=get_default_db_path().into_os_string())]
-    datadir: PathBuf,
-    /// rpc node to query from
-    #[arg(short, long)]
-    node: String,
+    local: Option<String>,
+    /// datadir for cache db, the working directory when absent
+    #[arg(long)]
+    datadir: Option<PathBuf>,
+    /// rpc nodes to query from, host:port or an http(s) url for the json gateway,
+    /// grpc://host:port or grpcs://host:port for grpc;
+    /// repeat or separate by commas to fail over between several
+    #[arg(short, long, value_delimiter = ',')]
+    node: Vec<NodeEndpoint>,
+    /// node api to use, auto prefers v2alpha1 when the node serves it
+    #[arg(long, value_enum)]
+    node_api: Option<ApiVersion>,
+    /// pem ca bundle to trust for https and grpcs nodes, e.g. of a self-signed node certificate
+    #[arg(long)]
+    node_ca: Option<PathBuf>,
+    /// credentials for every node, bearer:<token> or basic:<user>:<password>
+    #[arg(long)]
+    node_auth: Option<NodeAuth>,
+    #[command(subcommand)]
+    command: Command,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), sqlx::Error> {
-    let env = env_logger::Env::default().filter_or("RUST_LOG", "info");
-    env_logger::init_from_env(env);
-    let args = Cli::parse();
-    info!("{:?}", args);
-    let datadir = args.datadir;
-    let db_url = datadir.join("poolstats.sql");
-    let db_url = db_url.to_str().unwrap();
-    if !Sqlite::database_exists(db_url).await.unwrap() {
-        Sqlite::create_database(db_url).await.unwrap();
-    }
-    let poolstats = SqlitePool::connect(db_url).await.unwrap();
-    sqlx::migrate!().run(&poolstats).await.unwrap();
-    let db = SqlitePool::connect_lazy(&args.db)?;
-    let local = SqlitePool::connect_lazy(&args.local)?;
+#[derive(Args, Debug)]
+struct ServeArgs {
+    /// ip:port to listen for api request, or unix:/path/to.sock
+    #[arg(long)]
+    listen: Option<Listen>,
+    /// also sync in this process instead of a separate `sync`; `/events` needs it, as events are
+    /// only published within the syncing process
+    #[arg(long)]
+    sync: bool,
+    /// requests per second refilled for each client and route, 2 when absent
+    #[arg(long, value_parser = parse_rate)]
+    rate_limit: Option<f64>,
+    /// requests a client may burst on a route, 20 when absent
+    #[arg(long)]
+    rate_burst: Option<u32>,
+    /// burst override for one route, e.g. /nodes_info=5, or auth=5 for failed authentications
+    #[arg(long)]
+    route_burst: Vec<RouteBurst>,
+    /// origin allowed to call the api from a browser, repeatable, any when absent
+    #[arg(long, value_parser = parse_origin)]
+    cors_origin: Vec<HeaderValue>,
+    /// method allowed for cross-origin requests, repeatable, any when absent
+    #[arg(long, value_parser = parse_method)]
+    cors_method: Vec<Method>,
+    /// pem certificate chain, serves https together with --tls-key
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// pem private key, reloaded with the certificate when either file changes
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+}
 
-    let db_handler = DBHandler::new(db, local, poolstats);
-    let rpc_handler = RpcHandler::new(args.node);
-
-    let shared = Shared::new(db_handler, rpc_handler);
-
-    let fetch_resource = shared.clone();
-
-    tokio::spawn(async move {
-        loop {
-            let epoch_info = fetch_resource
-                .rpc_handler
-                .get_epoch()
-                .unwrap()
-                .epochnum
-                .number;
-            let round_id = (epoch_info - 1).to_string();
-            let mut next_round = None;
-            let mut next_epoch = None;
-            let current_layer = fetch_resource
-                .rpc_handler
-                .get_layer()
-                .unwrap()
-                .layernum
-                .number;
-            if current_layer >= epoch_info * 4032 + 2760 {
-                next_round = Some(epoch_info.to_string());
-                next_epoch = Some(epoch_info);
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// serve the api from the cache db, kept current by a `sync` process or `--sync`
+    Serve(ServeArgs),
+    /// copy registrations, atxs and smesher states into the cache db every sync interval
+    Sync {
+        /// a single pass, then exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// copy registrations and atxs of past epochs into the cache db, then exit
+    Backfill {
+        /// first epoch as reported by `/overview`
+        #[arg(long)]
+        from: i64,
+        /// last epoch, inclusive
+        #[arg(long)]
+        to: i64,
+    },
+    /// print the overview as a table, then exit
+    Report {
+        /// epoch as reported by `/overview`, the current one when absent
+        #[arg(long)]
+        epoch: Option<i64>,
+    },
+    /// check the dbs, their schemas and the nodes, exit with 1 when any check fails
+    Check,
+    /// write every key with its registration and atx for an epoch, then exit
+    Export {
+        /// epoch as reported by `/overview`
+        #[arg(long)]
+        epoch: i64,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// file to write, stdout when absent
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// manage api keys, then exit
+    Key {
+        #[command(subcommand)]
+        action: KeyCommand,
+    },
+}
+
+impl Command {
+    /// what has to be configured to run it
+    fn sources(&self) -> &'static [Source] {
+        match self {
+            Command::Serve(_) | Command::Sync { .. } | Command::Check => Source::ALL,
+            // backfill reads atxs from the chain, not the nodes
+            Command::Backfill { .. } => &[Source::Db, Source::Local],
+            Command::Report { .. } => &[Source::Local, Source::Nodes],
+            Command::Export { .. } => &[Source::Local],
+            Command::Key { .. } => &[],
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum KeyCommand {
+    /// create a key and print it, it cannot be shown again
+    Create {
+        #[arg(long)]
+        name: String,
+        #[arg(long, value_enum)]
+        scope: Scope,
+    },
+    Revoke {
+        #[arg(long)]
+        id: i64,
+    },
+    List,
+}
+
+async fn manage_keys(db: &DBHandler, action: KeyCommand) -> Result<(), sqlx::Error> {
+    match action {
+        KeyCommand::Create { name, scope } => {
+            let created = db.create_api_key(name, scope).await?;
+            println!("created api key {}: {}", created.id, created.key);
+        }
+        KeyCommand::Revoke { id } => {
+            if db.revoke_api_key(id).await? {
+                println!("revoked api key {}", id);
+            } else {
+                println!("no active api key {}", id);
             }
-            let count = fetch_resource
-                .db_handler
-                .count_initialzed()
-                .await
-                .unwrap_or(0);
-            let limit = 50;
-            for group in get_range(0..count, limit) {
-                if let Ok(keys) = fetch_resource
-                    .db_handler
-                    .get_init_keys(limit, group.start)
-                    .await
-                {
-                    info!("init posted keys {:?}", keys);
-                    for Key { id, num_units } in keys {
-                        match fetch_resource
-                            .db_handler
-                            .get_chain_registerations_by_id(id.clone(), round_id.clone())
-                            .await
-                        {
-                            Ok(registerations) => {
-                                if !registerations.is_empty() {
-                                    let _ = fetch_resource
-                                        .db_handler
-                                        .save_poet(id.clone(), num_units, registerations[0].clone())
-                                        .await;
-                                }
-                            }
-                            Err(_) => {}
-                        }
-                        match fetch_resource
-                            .db_handler
-                            .get_chain_atxs_by_id(id.clone(), epoch_info - 1)
-                            .await
-                        {
-                            Ok(atx) => {
-                                let _ = fetch_resource
-                                    .db_handler
-                                    .save_atx(id.clone(), num_units, atx)
-                                    .await;
-                            }
-                            Err(e) => {
-                                log::error!("{:?}", e)
-                            }
-                        }
-
-                        if let Some(round_id) = next_round.clone() {
-                            match fetch_resource
-                                .db_handler
-                                .get_chain_registerations_by_id(id.clone(), round_id.clone())
-                                .await
-                            {
-                                Ok(registerations) => {
-                                    if !registerations.is_empty() {
-                                        let _ = fetch_resource
-                                            .db_handler
-                                            .save_poet(
-                                                id.clone(),
-                                                num_units,
-                                                registerations[0].clone(),
-                                            )
-                                            .await;
-                                    }
-                                }
-                                Err(_) => {}
-                            }
-                        }
-
-                        if let Some(epoch) = next_epoch.clone() {
-                            match fetch_resource
-                                .db_handler
-                                .get_chain_atxs_by_id(id.clone(), epoch)
-                                .await
-                            {
-                                Ok(atx) => {
-                                    let _ = fetch_resource
-                                        .db_handler
-                                        .save_atx(id.clone(), num_units, atx)
-                                        .await;
-                                }
-                                Err(e) => {
-                                    log::error!("{:?}", e)
-                                }
-                            }
-                        }
-                    }
-                }
+        }
+        KeyCommand::List => {
+            for key in db.list_api_keys().await? {
+                let revoked = key
+                    .revoked_at
+                    .map(|at| format!("revoked at {}", at))
+                    .unwrap_or_default();
+                println!("{}\t{}\t{}\t{}", key.id, key.name, key.scope, revoked);
             }
-            sleep(Duration::from_secs(30 * 60)).await;
         }
-    });
+    }
+    Ok(())
+}
+
+async fn export(
+    shared: Arc<Shared>,
+    epoch: i64,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+) -> Result<(), sqlx::Error> {
+    let mut writer: Box<dyn AsyncWrite + Unpin> = match output {
+        Some(path) => Box::new(File::create(path).await?),
+        None => Box::new(tokio::io::stdout()),
+    };
+    let mut lines = pin!(export_lines(shared, epoch, format));
+    while let Some(line) = lines.next().await {
+        writer.write_all(line?.as_bytes()).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn backfill(shared: &Shared, from: i64, to: i64) -> anyhow::Result<()> {
+    if from > to {
+        bail!("--from {} is after --to {}", from, to);
+    }
+    for epoch in from..=to {
+        let backfilled = sync::backfill_epoch(shared, epoch).await?;
+        println!(
+            "epoch {}: {} keys, {} new registrations, {} new atxs",
+            epoch, backfilled.keys, backfilled.registrations, backfilled.atxs
+        );
+    }
+    Ok(())
+}
+
+fn print_overview(overview: &Overview, partial: bool) {
+    println!("epoch {}", overview.epoch);
+    println!(
+        "{:<14}{:>10}{:>12}{:>12}{:>12}",
+        "", "count", "num_units", "next_count", "next_units"
+    );
+    // unknown counts show as -
+    let item = |item: Option<&Item>, blank: &str| {
+        item.map_or((blank.to_string(), blank.to_string()), |item| {
+            (item.count.to_string(), item.num_units.to_string())
+        })
+    };
+    let row = |name: &str, current: Option<&Item>, next: Option<&Item>| {
+        let (current, next) = (item(current, "-"), item(next, ""));
+        println!(
+            "{:<14}{:>10}{:>12}{:>12}{:>12}",
+            name, current.0, current.1, next.0, next.1
+        );
+    };
+    row("initialized", overview.init_posted.as_ref(), None);
+    row("initializing", overview.initializing.as_ref(), None);
+    row(
+        "registered",
+        Some(&overview.registerd.current),
+        Some(&overview.registerd.next),
+    );
+    row(
+        "active",
+        Some(&overview.actived.current),
+        Some(&overview.actived.next),
+    );
+    if let Some(node) = &overview.node {
+        println!(
+            "node: {}, layer {} of {}, {} peers",
+            if node.state.synced {
+                "synced"
+            } else {
+                "syncing"
+            },
+            node.state.synced_layer,
+            node.state.top_layer,
+            node.state.connected_peers
+        );
+    }
+    if overview.unsynced == Some(true) {
+        println!("warning: the node is not synced, counts may be incomplete");
+    }
+    if partial {
+        println!("warning: some counts could not be read and show as 0");
+    }
+}
+
+async fn report(shared: &Shared, epoch: Option<i64>) -> anyhow::Result<()> {
+    if let Err(e) = shared.node.refresh(&shared.rpc_handler).await {
+        log::warn!("node status refresh failed: {:#}", e);
+    }
+    let overview = match epoch {
+        Some(epoch) => {
+            if !shared.db_handler.epoch_exists(epoch).await? {
+                bail!("no data stored for epoch {}", epoch);
+            }
+            get_overview(shared, epoch, false).await?
+        }
+        None => {
+            let clock = shared
+                .clock
+                .refresh(&shared.rpc_handler, &shared.config.network)
+                .await?;
+            get_overview(shared, clock.epoch - 1, true).await?
+        }
+    };
+    print_overview(&overview.data, overview.partial);
+    Ok(())
+}
+
+fn print_components(components: &BTreeMap<String, Component>) {
+    for (name, component) in components {
+        let status = match component.status {
+            ComponentStatus::Ok => "ok",
+            ComponentStatus::Warning => "warning",
+            ComponentStatus::Error => "error",
+        };
+        let details = component
+            .details
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "{:<14}{:<9}{}{}{}",
+            name,
+            status,
+            component.message.as_deref().unwrap_or_default(),
+            if component.message.is_some() && !details.is_empty() {
+                " "
+            } else {
+                ""
+            },
+            details
+        );
+    }
+}
+
+/// runs the loops that write the cache db until the process is stopped
+async fn sync_forever(shared: Arc<Shared>) {
+    tokio::join!(
+        sync::run(shared.clone()),
+        smesher::run(shared.clone()),
+        shared.rpc_handler.clone().watch(),
+    );
+}
+
+async fn sync_once(shared: &Shared) -> anyhow::Result<()> {
+    match smesher::refresh(shared).await {
+        Ok(count) => info!("refreshed {} smesher states", count),
+        Err(e) => log::warn!("smesher state refresh failed: {:#}", e),
+    }
+    let pass = sync::run_pass(shared, &mut SyncState::default()).await?;
+    info!(
+        "synced {} keys at epoch {} layer {}",
+        pass.keys, pass.epoch, pass.layer
+    );
+    Ok(())
+}
+
+async fn serve_api(shared: Arc<Shared>, sync: bool) -> anyhow::Result<()> {
+    let config = shared.config.clone();
+    let listen = config
+        .listen
+        .address
+        .ok_or_else(|| ConfigError::missing("listen.address", "--listen"))?;
+    tokio::spawn(clock::run(shared.clone()));
+    tokio::spawn(node::run(shared.clone()));
+    if sync {
+        tokio::spawn(sync_forever(shared.clone()));
+    } else {
+        tokio::spawn(shared.rpc_handler.clone().watch());
+    }
 
-    let router = Router::new()
-        .route("/overview", get(overview_handler))
-        .route("/nodes_info", post(get_nodes_info))
+    let limits = config.limits;
+    let limiter = RateLimiter::new(limits.rate_limit, limits.rate_burst, limits.route_bursts);
+    // routes whose data only changes with the cache and what `depends` lists
+    let tagged = |depends| middleware::from_fn_with_state((shared.clone(), depends), conditional);
+    let keys = Depends {
+        epoch: true,
+        post: true,
+        registrations: true,
+        node: false,
+    };
+    // probes are polled by the orchestrator, so they skip authentication; /readyz still queries
+    // the dbs and is limited by ip
+    let probes = Router::new()
+        .route(
+            "/readyz",
+            get(readyz_handler)
+                .route_layer(middleware::from_fn_with_state(limiter.clone(), rate_limit)),
+        )
+        .route("/healthz", get(healthz_handler));
+    // static assets, the dashboard sends the user's key with its own api calls
+    let assets = Router::new()
+        .route("/dashboard", get(dashboard::redirect_handler))
+        .route("/dashboard/", get(dashboard::index_handler))
+        .route("/dashboard/:name", get(dashboard::asset_handler));
+    let public = Router::new()
+        .route(
+            "/overview",
+            get(overview_handler).route_layer(tagged(Depends {
+                registrations: false,
+                node: true,
+                ..keys
+            })),
+        )
+        .route(
+            "/timeline",
+            get(timeline_handler).route_layer(tagged(Depends::default())),
+        )
+        .route("/clock", get(clock_handler))
+        .route("/sync_status", get(sync_status_handler))
+        .route("/metrics", get(metrics_handler))
+        .route_layer(middleware::from_fn_with_state(limiter.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(
+            (shared.clone(), Scope::Public),
+            require_scope,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            limiter.clone(),
+            limit_auth_failures,
+        ));
+    let private = Router::new()
+        .route(
+            "/nodes_info",
+            post(get_nodes_info).route_layer(tagged(keys)),
+        )
+        .route(
+            "/nodes_info/batch",
+            post(get_nodes_info_batch).route_layer(tagged(keys)),
+        )
+        .route(
+            "/events",
+            if sync {
+                get(events_handler)
+            } else {
+                get(events_unavailable_handler)
+            },
+        )
+        .route(
+            "/export/nodes",
+            get(export_handler).route_layer(tagged(keys)),
+        )
+        .route_layer(middleware::from_fn_with_state(limiter.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(
+            (shared.clone(), Scope::Private),
+            require_scope,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            limiter.clone(),
+            limit_auth_failures,
+        ));
+    let admin = Router::new()
+        .route(
+            "/admin/keys",
+            get(list_keys_handler).post(create_key_handler),
+        )
+        .route("/admin/keys/:id", delete(revoke_key_handler))
+        .route_layer(middleware::from_fn_with_state(limiter.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(
+            (shared.clone(), Scope::Admin),
+            require_scope,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            limiter.clone(),
+            limit_auth_failures,
+        ));
+
+    let api = public.merge(private).merge(admin);
+    let router = probes
+        .merge(assets)
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+        .nest(API_PREFIX, api.clone())
+        .merge(api)
         .with_state(shared)
         .layer(
             ServiceBuilder::new()
                 .layer(HandleErrorLayer::new(|_: BoxError| async {
-                    StatusCode::REQUEST_TIMEOUT
+                    ApiError::timeout()
                 }))
-                .layer(TimeoutLayer::new(Duration::from_secs(30))),
+                .layer(TimeoutLayer::new(Duration::from_secs(
+                    limits.request_timeout_secs,
+                ))),
         )
-        .layer(
-            CorsLayer::new()
-                .allow_methods(Any)
-                .allow_origin(Any)
-                .allow_headers(Any),
-        );
-    let listener = TcpListener::bind(&args.listen).await?;
-    info!("api server starts listening at {}", args.listen);
-    axum::serve(listener, router).await?;
+        .layer(CompressionLayer::new())
+        .layer(cors_layer(
+            &config.listen.cors_origins,
+            &config.listen.cors_methods,
+        ));
+    let tls = match (config.listen.tls_cert, config.listen.tls_key) {
+        (Some(cert), Some(key)) => {
+            let cert = ReloadingCert::load(cert, key)?;
+            tokio::spawn(cert.clone().watch());
+            Some(tls_acceptor(cert)?)
+        }
+        _ => None,
+    };
+    serve(router, listen, tls).await?;
     Ok(())
 }
+
+/// the cache db, created and migrated unless only `check`ed
+async fn open_cache(db_path: &Path, migrate: bool) -> anyhow::Result<SqlitePool> {
+    let db_url = db_path.to_str().unwrap();
+    if !migrate {
+        return Ok(SqlitePool::connect_lazy(db_url)?);
+    }
+    if !Sqlite::database_exists(db_url).await? {
+        Sqlite::create_database(db_url).await?;
+    }
+    let poolstats = SqlitePool::connect(db_url).await?;
+    sqlx::migrate!().run(&poolstats).await?;
+    Ok(poolstats)
+}
+
+#[tokio::main]
+async fn main() {
+    let env = env_logger::Env::default().filter_or("RUST_LOG", "info");
+    env_logger::init_from_env(env);
+    if let Err(e) = run(Cli::parse()).await {
+        log::error!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+/// the config file, then `POOLSTATS_*` variables, then flags
+fn configure(args: &mut Cli) -> anyhow::Result<Config> {
+    let path = args
+        .config
+        .take()
+        .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from));
+    // variables that are not unicode cannot be poolstats settings
+    let env = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    let mut config = Config::load(path.as_deref(), env)?;
+
+    let sources = &mut config.sources;
+    if let Some(db) = args.db.take() {
+        sources.db = Some(db);
+    }
+    if let Some(local) = args.local.take() {
+        sources.local = Some(local);
+    }
+    if let Some(datadir) = args.datadir.take() {
+        sources.datadir = Some(datadir);
+    }
+    if !args.node.is_empty() {
+        sources.nodes = std::mem::take(&mut args.node);
+    }
+    if let Some(node_api) = args.node_api {
+        sources.node_api = node_api;
+    }
+    if let Some(node_ca) = args.node_ca.take() {
+        sources.node_ca = Some(node_ca);
+    }
+    if let Some(node_auth) = args.node_auth.take() {
+        config.auth.node = Some(node_auth);
+    }
+    if let Command::Serve(serve) = &mut args.command {
+        let listen = &mut config.listen;
+        if let Some(address) = serve.listen.take() {
+            listen.address = Some(address);
+        }
+        if !serve.cors_origin.is_empty() {
+            listen.cors_origins = std::mem::take(&mut serve.cors_origin);
+        }
+        if !serve.cors_method.is_empty() {
+            listen.cors_methods = std::mem::take(&mut serve.cors_method);
+        }
+        if let (Some(cert), Some(key)) = (serve.tls_cert.take(), serve.tls_key.take()) {
+            listen.tls_cert = Some(cert);
+            listen.tls_key = Some(key);
+        }
+        let limits = &mut config.limits;
+        if let Some(rate_limit) = serve.rate_limit {
+            limits.rate_limit = rate_limit;
+        }
+        if let Some(rate_burst) = serve.rate_burst {
+            limits.rate_burst = rate_burst;
+        }
+        if !serve.route_burst.is_empty() {
+            limits.route_bursts = std::mem::take(&mut serve.route_burst);
+        }
+    }
+    config.validate(args.command.sources())?;
+    Ok(config)
+}
+
+async fn run(mut args: Cli) -> anyhow::Result<()> {
+    let config = configure(&mut args)?;
+    info!("{:?}", config);
+    let check = matches!(args.command, Command::Check);
+    let cache = config.datadir().join("poolstats.sql");
+    let poolstats = open_cache(&cache, !check).await?;
+    let sources = &config.sources;
+    let db = SqlitePool::connect_lazy(sources.db.as_deref().unwrap_or_default())?;
+    // attaching a missing cache would create it, which `check` only reports
+    let local = DBHandler::connect_local(
+        sources.local.as_deref().unwrap_or_default(),
+        (!check).then_some(cache.as_path()),
+    )?;
+
+    let db_handler = DBHandler::new(db, local, poolstats);
+    let ca = match &sources.node_ca {
+        Some(path) => Some(
+            std::fs::read(path)
+                .with_context(|| format!("cannot read node ca {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let options = RpcOptions {
+        version: sources.node_api,
+        ca,
+        auth: config.auth.node.clone(),
+        timeout: config.limits.rpc_timeout(),
+        retry_attempts: config.limits.rpc_retry_attempts,
+        breaker_threshold: config.limits.breaker_threshold,
+        breaker_cooldown: config.limits.breaker_cooldown(),
+        max_layer_divergence: config.alerts.max_layer_divergence,
+        agreement_interval: config.intervals.agreement(),
+    };
+    let rpc_handler = RpcHandler::with_options(sources.nodes.clone(), options)
+        .context("invalid node settings")?;
+
+    let (db, local) = (sources.db.clone(), sources.local.clone());
+    let shared = Shared::new(db_handler, rpc_handler, config);
+
+    match args.command {
+        Command::Serve(serve_args) => serve_api(shared, serve_args.sync).await,
+        Command::Sync { once: true } => sync_once(&shared).await,
+        Command::Sync { once: false } => {
+            sync_forever(shared).await;
+            Ok(())
+        }
+        Command::Backfill { from, to } => backfill(&shared, from, to).await,
+        Command::Report { epoch } => report(&shared, epoch).await,
+        Command::Check => {
+            println!("chain db: {}", db.unwrap_or_default());
+            println!("local db: {}", local.unwrap_or_default());
+            let components = health::check(&shared).await;
+            print_components(&components);
+            if !components.values().all(Component::is_ok) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Export {
+            epoch,
+            format,
+            output,
+        } => Ok(export(shared, epoch, format, output).await?),
+        Command::Key { action } => Ok(manage_keys(&shared.db_handler, action).await?),
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+use crate::{
+    clock::unix_now,
+    error::ApiError,
+    poolstats::{get_overview, GeneralItem},
+    Shared,
+};
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    pub registry: Registry,
+    pub initialized_count: IntGauge,
+    pub initialized_num_units: IntGauge,
+    pub initializing_count: IntGauge,
+    pub initializing_num_units: IntGauge,
+    /// labelled by `epoch`: current or next
+    pub registered_count: IntGaugeVec,
+    pub registered_num_units: IntGaugeVec,
+    pub active_count: IntGaugeVec,
+    pub active_num_units: IntGaugeVec,
+    pub current_epoch: IntGauge,
+    pub current_layer: IntGauge,
+    pub node_synced: IntGauge,
+    pub node_connected_peers: IntGauge,
+    pub node_top_layer: IntGauge,
+    pub node_verified_layer: IntGauge,
+    pub sync_pass_duration: Gauge,
+    pub sync_last_success: IntGauge,
+    pub rpc_latency: HistogramVec,
+    pub rpc_errors: IntCounterVec,
+    pub rpc_retries: IntCounterVec,
+    /// 1 while the circuit breaker of the endpoint rejects requests
+    pub rpc_circuit_open: IntGaugeVec,
+    pub db_errors: IntCounterVec,
+    /// labelled by route and client kind: ip or key
+    pub rate_limited: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("poolstats".into()), None).unwrap();
+        let int_gauge = |name: &str, help: &str| {
+            let gauge = IntGauge::new(name, help).unwrap();
+            registry.register(Box::new(gauge.clone())).unwrap();
+            gauge
+        };
+        let epoch_gauge = |name: &str, help: &str| {
+            let gauge = IntGaugeVec::new(Opts::new(name, help), &["epoch"]).unwrap();
+            registry.register(Box::new(gauge.clone())).unwrap();
+            gauge
+        };
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter
+        };
+        let sync_pass_duration = Gauge::new(
+            "sync_pass_duration_seconds",
+            "duration of the last completed sync pass",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(sync_pass_duration.clone()))
+            .unwrap();
+        let rpc_latency = HistogramVec::new(
+            HistogramOpts::new("rpc_request_duration_seconds", "node rpc request latency"),
+            &["method"],
+        )
+        .unwrap();
+        registry.register(Box::new(rpc_latency.clone())).unwrap();
+        let rpc_circuit_open = IntGaugeVec::new(
+            Opts::new(
+                "rpc_circuit_open",
+                "node endpoints whose circuit breaker is open",
+            ),
+            &["endpoint"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(rpc_circuit_open.clone()))
+            .unwrap();
+        let db_errors = counter("db_errors_total", "failed sqlite queries", &["pool"]);
+        for pool in ["chain", "local", "poolstats"] {
+            db_errors.with_label_values(&[pool]);
+        }
+        Self {
+            initialized_count: int_gauge("initialized_count", "keys in the local post table"),
+            initialized_num_units: int_gauge(
+                "initialized_num_units",
+                "num_units of keys in the local post table",
+            ),
+            initializing_count: int_gauge(
+                "initializing_count",
+                "identities whose post setup is not complete",
+            ),
+            initializing_num_units: int_gauge(
+                "initializing_num_units",
+                "num_units of identities whose post setup is not complete",
+            ),
+            registered_count: epoch_gauge("registered_count", "keys registered to poet"),
+            registered_num_units: epoch_gauge(
+                "registered_num_units",
+                "num_units of keys registered to poet",
+            ),
+            active_count: epoch_gauge("active_count", "keys with a published atx"),
+            active_num_units: epoch_gauge(
+                "active_num_units",
+                "effective_num_units of keys with a published atx",
+            ),
+            current_epoch: int_gauge("current_epoch", "current epoch reported by the node"),
+            current_layer: int_gauge("current_layer", "current layer reported by the node"),
+            node_synced: int_gauge("node_synced", "1 when the node reports it is synced"),
+            node_connected_peers: int_gauge("node_connected_peers", "peers of the node"),
+            node_top_layer: int_gauge("node_top_layer", "top layer known to the node"),
+            node_verified_layer: int_gauge("node_verified_layer", "layer verified by the node"),
+            sync_pass_duration,
+            sync_last_success: int_gauge(
+                "sync_last_success_timestamp_seconds",
+                "unix time the last sync pass completed",
+            ),
+            rpc_latency,
+            rpc_errors: counter("rpc_errors_total", "failed node rpc requests", &["method"]),
+            rpc_retries: counter(
+                "rpc_retries_total",
+                "node rpc requests retried after a transient failure",
+                &["method"],
+            ),
+            rpc_circuit_open,
+            db_errors,
+            rate_limited: counter(
+                "rate_limited_total",
+                "requests rejected by the rate limiter",
+                &["route", "client"],
+            ),
+            registry,
+        }
+    }
+
+    pub fn sync_pass_completed(&self, duration: Duration) {
+        self.sync_pass_duration.set(duration.as_secs_f64());
+        self.sync_last_success.set(unix_now());
+    }
+
+    fn set_general(count: &IntGaugeVec, num_units: &IntGaugeVec, item: &GeneralItem) {
+        for (epoch, item) in [("current", &item.current), ("next", &item.next)] {
+            count.with_label_values(&[epoch]).set(item.count);
+            num_units.with_label_values(&[epoch]).set(item.num_units);
+        }
+    }
+}
+
+/// counts failed queries per pool, a missing row is an answer rather than a failure
+pub trait TrackDbError {
+    fn track(self, pool: &str) -> Self;
+}
+
+impl<T> TrackDbError for Result<T, sqlx::Error> {
+    fn track(self, pool: &str) -> Self {
+        if let Err(e) = &self {
+            if !matches!(e, sqlx::Error::RowNotFound) {
+                METRICS.db_errors.with_label_values(&[pool]).inc();
+            }
+        }
+        self
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "pool",
+    responses(
+        (status = 200, description = "prometheus text exposition", content_type = "text/plain", body = String),
+    )
+)]
+pub async fn metrics_handler(State(shared): State<Arc<Shared>>) -> Result<Response, ApiError> {
+    // db failures are part of what is being scraped, so they only skip their gauges
+    if let Ok(Some(pass)) = shared.db_handler.last_sync_pass().await {
+        // a separate `sync` process completed a pass this one did not see
+        if pass.finished_at > METRICS.sync_last_success.get() {
+            METRICS.sync_last_success.set(pass.finished_at);
+            METRICS
+                .sync_pass_duration
+                .set((pass.finished_at - pass.started_at) as f64);
+        }
+    }
+    if let Some(clock) = shared.clock.get() {
+        if let Ok(overview) = get_overview(&shared, clock.epoch - 1, true).await {
+            let overview = overview.data;
+            if let Some(init_posted) = &overview.init_posted {
+                METRICS.initialized_count.set(init_posted.count);
+                METRICS.initialized_num_units.set(init_posted.num_units);
+            }
+            if let Some(initializing) = &overview.initializing {
+                METRICS.initializing_count.set(initializing.count);
+                METRICS.initializing_num_units.set(initializing.num_units);
+            }
+            Metrics::set_general(
+                &METRICS.registered_count,
+                &METRICS.registered_num_units,
+                &overview.registerd,
+            );
+            Metrics::set_general(
+                &METRICS.active_count,
+                &METRICS.active_num_units,
+                &overview.actived,
+            );
+        }
+    }
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "metrics_encoding", e))?;
+    Ok(([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], buffer).into_response())
+}
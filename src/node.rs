@@ -0,0 +1,86 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_now,
+    metrics::METRICS,
+    rpc::{NodeState, RpcHandler},
+    Shared,
+};
+
+pub const NODE_STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct NodeStatusInfo {
+    #[serde(flatten)]
+    pub state: NodeState,
+    /// unix time of the rpc answer
+    pub refreshed_at: i64,
+}
+
+/// last sync state, peers and layers seen from the node, read by handlers without any io
+#[derive(Debug, Default)]
+pub struct NodeMonitor {
+    state: RwLock<Option<NodeStatusInfo>>,
+}
+
+impl NodeMonitor {
+    pub fn get(&self) -> Option<NodeStatusInfo> {
+        self.state.read().unwrap().clone()
+    }
+
+    /// registrations and atxs are read from the node's db, so counts are only complete once it synced;
+    /// an unknown status counts as unsynced
+    pub fn unsynced(&self) -> bool {
+        !self
+            .state
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|info| info.state.synced)
+    }
+
+    pub fn set(&self, state: NodeState) -> NodeStatusInfo {
+        let info = NodeStatusInfo {
+            state,
+            refreshed_at: unix_now(),
+        };
+        let previous = self.state.write().unwrap().replace(info.clone());
+        let was_synced = previous.map(|previous| previous.state.synced);
+        if was_synced != Some(info.state.synced) {
+            if info.state.synced {
+                log::info!("node is synced at layer {}", info.state.synced_layer);
+            } else {
+                log::warn!(
+                    "node is not synced, at layer {} of {}, counts may be incomplete",
+                    info.state.synced_layer,
+                    info.state.top_layer
+                );
+            }
+        }
+        METRICS.node_synced.set(info.state.synced as i64);
+        METRICS.node_connected_peers.set(info.state.connected_peers);
+        METRICS.node_top_layer.set(info.state.top_layer);
+        METRICS.node_verified_layer.set(info.state.verified_layer);
+        info
+    }
+
+    pub async fn refresh(&self, rpc: &RpcHandler) -> anyhow::Result<NodeStatusInfo> {
+        Ok(self.set(rpc.node_status().await?))
+    }
+}
+
+pub async fn run(shared: Arc<Shared>) {
+    loop {
+        if let Err(e) = shared.node.refresh(&shared.rpc_handler).await {
+            log::warn!("node status refresh failed: {:?}", e);
+        }
+        sleep(shared.config.intervals.node_status()).await;
+    }
+}
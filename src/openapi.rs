@@ -0,0 +1,142 @@
+use utoipa::{
+    openapi::security::{
+        ApiKey as ApiKeyScheme, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme,
+    },
+    Modify, OpenApi, ToSchema,
+};
+
+use crate::{
+    auth::{self, ApiKey, CreateApiKeyRequest, CreatedApiKey, Scope, API_KEY_HEADER},
+    clock::{self, ClockInfo, ClockState},
+    events::{self, PoolEvent},
+    export::{self, ExportFormat},
+    metrics,
+    node::NodeStatusInfo,
+    poolstats::{
+        self, AtxInfo, BatchNodesInfo, BatchRequest, GeneralItem, GeneralRequest, InvalidId, Item,
+        NodeInfo, NodeStatus, NodesInfo, Overview, Registeration, SortBy, SortOrder,
+    },
+    rpc::{EndpointHealth, NodeState, PostSetupState},
+    smesher::SmesherState,
+    sync::{self, SyncPass, SyncStatus},
+    timeline::{self, TimelineEntry},
+};
+
+/// every documented route is served under this prefix, the bare paths are kept as aliases
+pub const API_PREFIX: &str = "/v1";
+
+/// success body written by `ApiResponse`
+#[derive(ToSchema)]
+#[aliases(
+    OverviewResponse = Envelope<Overview>,
+    NodesInfoResponse = Envelope<NodesInfo>,
+    BatchNodesInfoResponse = Envelope<BatchNodesInfo>,
+    TimelineResponse = Envelope<Vec<TimelineEntry>>,
+    ClockResponse = Envelope<ClockInfo>,
+    SyncStatusResponse = Envelope<SyncStatus>,
+    ApiKeysResponse = Envelope<Vec<ApiKey>>,
+    CreatedApiKeyResponse = Envelope<CreatedApiKey>,
+    RevokedApiKeyResponse = Envelope<i64>
+)]
+pub struct Envelope<T> {
+    /// always 200
+    pub code: u16,
+    pub data: T,
+    /// some of the data could not be loaded and is zeroed
+    pub partial: bool,
+}
+
+/// body written by `ApiError`
+#[derive(ToSchema)]
+pub struct ErrorBody {
+    /// http status
+    pub code: u16,
+    /// machine readable, stable across releases
+    pub error: String,
+    pub message: String,
+}
+
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKeyScheme::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "poolstats", description = "statistics of the keys initialized by a spacemesh node"),
+    servers((url = "/v1")),
+    paths(
+        poolstats::overview_handler,
+        timeline::timeline_handler,
+        clock::clock_handler,
+        sync::sync_status_handler,
+        metrics::metrics_handler,
+        poolstats::get_nodes_info,
+        poolstats::get_nodes_info_batch,
+        events::events_handler,
+        export::export_handler,
+        auth::list_keys_handler,
+        auth::create_key_handler,
+        auth::revoke_key_handler,
+    ),
+    components(schemas(
+        ErrorBody,
+        OverviewResponse,
+        NodesInfoResponse,
+        BatchNodesInfoResponse,
+        TimelineResponse,
+        ClockResponse,
+        SyncStatusResponse,
+        ApiKeysResponse,
+        CreatedApiKeyResponse,
+        RevokedApiKeyResponse,
+        Overview,
+        Item,
+        GeneralItem,
+        GeneralRequest,
+        NodeStatus,
+        SortBy,
+        SortOrder,
+        NodesInfo,
+        NodeInfo,
+        Registeration,
+        AtxInfo,
+        SmesherState,
+        PostSetupState,
+        NodeStatusInfo,
+        NodeState,
+        BatchRequest,
+        BatchNodesInfo,
+        InvalidId,
+        TimelineEntry,
+        ClockInfo,
+        ClockState,
+        SyncStatus,
+        SyncPass,
+        EndpointHealth,
+        PoolEvent,
+        ExportFormat,
+        ApiKey,
+        CreatedApiKey,
+        CreateApiKeyRequest,
+        Scope,
+    )),
+    modifiers(&Security),
+    tags(
+        (name = "pool", description = "aggregate numbers, no key needed"),
+        (name = "nodes", description = "per-node data, needs a private key"),
+        (name = "admin", description = "key management, needs an admin key"),
+    )
+)]
+pub struct ApiDoc;
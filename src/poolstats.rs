@@ -1,20 +1,133 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use axum::{
-    extract::{self, State},
-    response::IntoResponse,
-    Json,
+    extract::{
+        self,
+        rejection::{JsonRejection, QueryRejection},
+        Query, State,
+    },
+    http::StatusCode,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    error::{ApiError, ApiResponse, Partial},
+    metrics::TrackDbError,
+    node::NodeStatusInfo,
+    smesher::SmesherState,
+    DBHandler, Shared,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    /// published an atx for the epoch
+    Active,
+    /// registered to poet but no atx yet
+    #[serde(alias = "registered_only")]
+    Registered,
+    /// neither registered nor published
+    Missing,
+}
+
+impl NodeStatus {
+    pub(crate) fn of(has_atx: bool, registered: bool) -> Self {
+        match (has_atx, registered) {
+            (true, _) => NodeStatus::Active,
+            (false, true) => NodeStatus::Registered,
+            (false, false) => NodeStatus::Missing,
+        }
+    }
+}
 
-use crate::{DBHandler, Shared};
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Id,
+    NumUnits,
+    EffectiveNumUnits,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// opaque keyset position: the sort value and id of the last node returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub sort_by: SortBy,
+    pub value: i64,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let sort_by = serde_json::to_value(self.sort_by).unwrap_or_default();
+        let raw = format!(
+            "{}:{}:{}",
+            sort_by.as_str().unwrap_or_default(),
+            self.value,
+            self.id
+        );
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let mut parts = raw.splitn(3, ':');
+        let sort_by = serde_json::from_value(json!(parts.next()?)).ok()?;
+        let value = parts.next()?.parse().ok()?;
+        let id = parts.next()?.to_string();
+        hex::decode(&id).ok()?;
+        Some(Self { sort_by, value, id })
+    }
+
+    pub fn of(sort_by: SortBy, key: &Key, atx: &AtxInfo) -> Self {
+        let value = match sort_by {
+            SortBy::Id => 0,
+            SortBy::NumUnits => key.num_units,
+            SortBy::EffectiveNumUnits => atx.effective_num_units,
+        };
+        Self {
+            sort_by,
+            value,
+            id: key.id.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
 pub struct GeneralRequest {
+    /// capped at `limits.max_page_size`, follow `next_cursor` for the rest
     pub limit: i64,
+    /// skipped rows when there is no `cursor`; deprecated, deep offsets cost a scan of every
+    /// skipped row
+    #[serde(default)]
+    #[schema(deprecated)]
     pub offset: i64,
+    /// `next_cursor` of the previous page, takes the place of `offset`
+    pub cursor: Option<String>,
+    pub status: Option<NodeStatus>,
+    pub coinbase: Option<String>,
+    pub min_num_units: Option<i64>,
+    pub max_num_units: Option<i64>,
+    pub id_prefix: Option<String>,
+    #[serde(default)]
+    pub sort_by: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
@@ -23,14 +136,14 @@ pub struct Key {
     pub num_units: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow, ToSchema)]
 pub struct Registeration {
     pub address: String,
     pub round_id: String,
     pub round_end: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, FromRow, ToSchema)]
 pub struct AtxInfo {
     pub epoch: i64,
     pub atx_id: String,
@@ -44,93 +157,146 @@ impl AtxInfo {
     }
 }
 
-impl Default for AtxInfo {
-    fn default() -> Self {
-        Self {
-            epoch: 0,
-            atx_id: Default::default(),
-            effective_num_units: 0,
-            coinbase: Default::default(),
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct KeyAtx {
+    pub id: String,
+    #[sqlx(flatten)]
+    pub atx: AtxInfo,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct NodeInfo {
     pub id: String,
     pub num_units: i64,
+    pub status: NodeStatus,
     pub registerations: Vec<Registeration>,
     pub atx: AtxInfo,
+    /// post setup and smeshing state, unknown until the node reported the identity
+    pub smesher: Option<SmesherState>,
 }
 
 impl NodeInfo {
-    fn new(id: String, num_units: i64, registerations: Vec<Registeration>, atx: AtxInfo) -> Self {
+    fn new(
+        id: String,
+        num_units: i64,
+        status: NodeStatus,
+        registerations: Vec<Registeration>,
+        atx: AtxInfo,
+        smesher: Option<SmesherState>,
+    ) -> Self {
         Self {
             id,
             num_units,
+            status,
             registerations,
             atx,
+            smesher,
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct Item {
     pub count: i64,
     pub num_units: i64,
 }
 
 impl Item {
-    fn new(count: i64, num_units: i64) -> Self {
+    pub fn new(count: i64, num_units: i64) -> Self {
         Self { count, num_units }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct GeneralItem {
     pub current: Item,
     pub next: Item,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OverviewQuery {
+    pub epoch: Option<i64>,
+    /// poet round, reported alongside the epoch with the same number
+    pub round: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct Overview {
-    pub init_posted: Item,
+    /// epoch reported as `current`, `next` is the one after it
+    pub epoch: i64,
+    /// for a past epoch the totals snapshotted while it was current, none when there is no snapshot
+    pub init_posted: Option<Item>,
+    /// identities whose post setup is not complete yet as reported now, none for a past epoch
+    pub initializing: Option<Item>,
     pub registerd: GeneralItem,
     pub actived: GeneralItem,
+    /// latest status of the node, none until it answered and for a past epoch
+    pub node: Option<NodeStatusInfo>,
+    /// the node is not synced or its status is unknown, registration and atx counts may be
+    /// incomplete; none for a past epoch
+    pub unsynced: Option<bool>,
 }
 
-impl IntoResponse for Overview {
-    fn into_response(self) -> axum::response::Response {
-        Json(json!({"code": 200, "data": self})).into_response()
-    }
+/// upper bound of ids in one batch lookup, default of `limits.max_batch_ids`
+pub const MAX_BATCH_IDS: usize = 1000;
+/// upper bound of nodes in one `/nodes_info` page, default of `limits.max_page_size`
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct BatchRequest {
+    pub ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct InvalidId {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct BatchNodesInfo {
+    pub found: Vec<NodeInfo>,
+    pub not_found: Vec<String>,
+    pub invalid: Vec<InvalidId>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct NodesInfo {
+    pub total: usize,
+    pub next_cursor: Option<String>,
+    pub data: Vec<NodeInfo>,
 }
 
 impl DBHandler {
+    /// returns whether the registration was new
     pub async fn save_poet(
         &self,
         id: String,
         num_unit: i64,
         poet: Registeration,
-    ) -> Result<(), sqlx::Error> {
-        let _ = sqlx::query(
-            "INSERT INTO poet_registration (id, round_id, num_unit) VALUES ($1, $2, $3)",
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO poet_registration (id, round_id, num_unit) VALUES ($1, $2, $3)",
         )
         .bind(id)
         .bind(poet.round_id)
         .bind(num_unit)
-        .fetch_one(&self.poolstats)
-        .await?;
-        Ok(())
+        .execute(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(result.rows_affected() > 0)
     }
 
+    /// returns whether the atx was new
     pub async fn save_atx(
         &self,
         id: String,
         num_unit: i64,
         atx: AtxInfo,
-    ) -> Result<(), sqlx::Error> {
-        let _ = sqlx::query(
-            "INSERT INTO atxs (id, epoch, effective_num_units, coinbase, atx_id, num_unit) VALUES ($1, $2, $3, $4, $5, $6)",
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO atxs (id, epoch, effective_num_units, coinbase, atx_id, num_unit) VALUES ($1, $2, $3, $4, $5, $6)",
         )
         .bind(id)
         .bind(atx.epoch)
@@ -138,9 +304,10 @@ impl DBHandler {
         .bind(atx.coinbase)
         .bind(atx.atx_id)
         .bind(num_unit)
-        .fetch_one(&self.poolstats)
-        .await?;
-        Ok(())
+        .execute(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(result.rows_affected() > 0)
     }
 }
 
@@ -149,16 +316,19 @@ impl DBHandler {
         let result = sqlx::query_scalar("SELECT COUNT (*) FROM atxs WHERE epoch = $1")
             .bind(epoch)
             .fetch_one(&self.poolstats)
-            .await?;
+            .await
+            .track("poolstats")?;
         Ok(result)
     }
 
     pub async fn actived_num_units(&self, epoch: i64) -> Result<i64, sqlx::Error> {
-        let result =
-            sqlx::query_scalar("SELECT SUM (effective_num_units) FROM atxs WHERE epoch = $1")
-                .bind(epoch)
-                .fetch_one(&self.poolstats)
-                .await?;
+        let result = sqlx::query_scalar(
+            "SELECT COALESCE (SUM (effective_num_units), 0) FROM atxs WHERE epoch = $1",
+        )
+        .bind(epoch)
+        .fetch_one(&self.poolstats)
+        .await
+        .track("poolstats")?;
         Ok(result)
     }
 
@@ -167,16 +337,19 @@ impl DBHandler {
             sqlx::query_scalar("SELECT COUNT (*) FROM poet_registration WHERE round_id = $1")
                 .bind(round_id)
                 .fetch_one(&self.poolstats)
-                .await?;
+                .await
+                .track("poolstats")?;
         Ok(result)
     }
 
     pub async fn registered_num_units(&self, round_id: String) -> Result<i64, sqlx::Error> {
-        let result =
-            sqlx::query_scalar("SELECT SUM (num_unit) FROM poet_registration WHERE round_id = $1")
-                .bind(round_id)
-                .fetch_one(&self.poolstats)
-                .await?;
+        let result = sqlx::query_scalar(
+            "SELECT COALESCE (SUM (num_unit), 0) FROM poet_registration WHERE round_id = $1",
+        )
+        .bind(round_id)
+        .fetch_one(&self.poolstats)
+        .await
+        .track("poolstats")?;
         Ok(result)
     }
 
@@ -184,67 +357,131 @@ impl DBHandler {
         let result = sqlx::query_as(
             "SELECT epoch, atx_id, effective_num_units, coinbase FROM atxs WHERE id = $1 AND epoch = $2",
         )
-        .bind(hex::decode(id).unwrap())
+        .bind(id)
         .bind(epoch)
         .fetch_one(&self.poolstats)
-        .await?;
+        .await
+        .track("poolstats")?;
+        Ok(result)
+    }
+
+    pub async fn get_atxs_by_epoch(&self, epoch: i64) -> Result<Vec<KeyAtx>, sqlx::Error> {
+        let result = sqlx::query_as(
+            "SELECT id, epoch, atx_id, effective_num_units, coinbase FROM atxs WHERE epoch = $1",
+        )
+        .bind(epoch)
+        .fetch_all(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(result)
+    }
+
+    pub async fn epoch_exists(&self, epoch: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM atxs WHERE epoch = $1) OR EXISTS (SELECT 1 FROM poet_registration WHERE round_id = $2)",
+        )
+        .bind(epoch)
+        .bind(epoch.to_string())
+        .fetch_one(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(result)
+    }
+
+    pub async fn get_registered_ids(&self, round_id: String) -> Result<Vec<String>, sqlx::Error> {
+        let result = sqlx::query_scalar("SELECT id FROM poet_registration WHERE round_id = $1")
+            .bind(round_id)
+            .fetch_all(&self.poolstats)
+            .await
+            .track("poolstats")?;
         Ok(result)
     }
 }
 
-pub async fn overview_handler(State(shared): State<Arc<Shared>>) -> impl IntoResponse {
-    let init_posted_count = shared.db_handler.count_initialzed().await.unwrap_or(0);
-    let init_posted_num_units = shared.db_handler.inited_num_units().await.unwrap_or(0);
+#[utoipa::path(
+    get,
+    path = "/overview",
+    tag = "pool",
+    params(OverviewQuery),
+    responses(
+        (status = 200, body = OverviewResponse),
+        (status = 304, description = "unchanged since the etag in If-None-Match"),
+        (status = 400, description = "epoch and round both given", body = ErrorBody),
+        (status = 404, description = "nothing stored for the epoch", body = ErrorBody),
+        (status = 503, description = "database or chain clock unavailable", body = ErrorBody),
+    )
+)]
+pub async fn overview_handler(
+    State(shared): State<Arc<Shared>>,
+    query: Result<Query<OverviewQuery>, QueryRejection>,
+) -> Result<ApiResponse<Overview>, ApiError> {
+    let Query(query) = query?;
+    let epoch = match (query.epoch, query.round) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::bad_request(
+                "invalid_query",
+                "epoch and round are mutually exclusive",
+            ))
+        }
+        (Some(epoch), None) | (None, Some(epoch)) => {
+            if !shared.db_handler.epoch_exists(epoch).await? {
+                return Err(ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    "epoch_not_found",
+                    format!("no data stored for epoch {}", epoch),
+                ));
+            }
+            return get_overview(&shared, epoch, false).await;
+        }
+        (None, None) => shared.clock.current()?.epoch - 1,
+    };
+    get_overview(&shared, epoch, true).await
+}
 
-    let epoch_info = shared.rpc_handler.get_epoch().unwrap().epochnum.number;
-    let round_id = (epoch_info - 1).to_string();
+/// overview with `epoch` as current; `live` adds the initialized totals and node state of now,
+/// otherwise only the initialized totals snapshotted for `epoch` are known
+pub async fn get_overview(
+    shared: &Shared,
+    epoch: i64,
+    live: bool,
+) -> Result<ApiResponse<Overview>, ApiError> {
+    let db = &shared.db_handler;
+    let round_id = epoch.to_string();
+    let next_round_id = (epoch + 1).to_string();
 
-    let registed_count = shared
-        .db_handler
-        .count_registered(round_id.clone())
-        .await
-        .unwrap_or(0);
-    let registed_num_units = shared
-        .db_handler
-        .registered_num_units(round_id.clone())
-        .await
-        .unwrap_or(0);
+    let mut partial = Partial::default();
+    let (init_posted, initializing) = if live {
+        let init_posted = Item::new(
+            partial.check(db.count_initialzed().await),
+            partial.check(db.inited_num_units().await),
+        );
+        let initializing = Item::new(
+            partial.check(db.count_initializing().await),
+            partial.check(db.initializing_num_units().await),
+        );
+        (Some(init_posted), Some(initializing))
+    } else {
+        (partial.check(db.get_init_snapshot(epoch).await), None)
+    };
 
-    let next_round_id = epoch_info.to_string();
-    let next_registed_count = shared
-        .db_handler
-        .count_registered(next_round_id.clone())
-        .await
-        .unwrap_or(0);
-    let next_registed_num_units = shared
-        .db_handler
-        .registered_num_units(next_round_id.clone())
-        .await
-        .unwrap_or(0);
+    let registed_count = partial.check(db.count_registered(round_id.clone()).await);
+    let registed_num_units = partial.check(db.registered_num_units(round_id.clone()).await);
+    let next_registed_count = partial.check(db.count_registered(next_round_id.clone()).await);
+    let next_registed_num_units =
+        partial.check(db.registered_num_units(next_round_id.clone()).await);
 
-    let actived_count = shared
-        .db_handler
-        .count_activated(epoch_info - 1)
-        .await
-        .unwrap_or(0);
-    let actived_num_units = shared
-        .db_handler
-        .actived_num_units(epoch_info - 1)
-        .await
-        .unwrap_or(0);
+    let actived_count = partial.check(db.count_activated(epoch).await);
+    let actived_num_units = partial.check(db.actived_num_units(epoch).await);
+    let next_actived_count = partial.check(db.count_activated(epoch + 1).await);
+    let next_actived_num_units = partial.check(db.actived_num_units(epoch + 1).await);
+    if partial.all_failed() {
+        return Err(ApiError::database("every overview query failed"));
+    }
 
-    let next_actived_count = shared
-        .db_handler
-        .count_activated(epoch_info)
-        .await
-        .unwrap_or(0);
-    let next_actived_num_units = shared
-        .db_handler
-        .actived_num_units(epoch_info)
-        .await
-        .unwrap_or(0);
-    Overview {
-        init_posted: Item::new(init_posted_count, init_posted_num_units),
+    let overview = Overview {
+        epoch,
+        init_posted,
+        initializing,
         registerd: GeneralItem {
             current: Item::new(registed_count, registed_num_units),
             next: Item::new(next_registed_count, next_registed_num_units),
@@ -253,39 +490,197 @@ pub async fn overview_handler(State(shared): State<Arc<Shared>>) -> impl IntoRes
             current: Item::new(actived_count, actived_num_units),
             next: Item::new(next_actived_count, next_actived_num_units),
         },
-    }
+        node: shared.node.get().filter(|_| live),
+        unsynced: live.then(|| shared.node.unsynced()),
+    };
+    Ok(ApiResponse::new(overview, partial.is_partial()))
 }
 
+#[utoipa::path(
+    post,
+    path = "/nodes_info",
+    tag = "nodes",
+    request_body = GeneralRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, body = NodesInfoResponse),
+        (status = 304, description = "unchanged since the etag in If-None-Match"),
+        (status = 400, description = "invalid cursor or filter", body = ErrorBody),
+        (status = 422, description = "malformed request body", body = ErrorBody),
+        (status = 503, description = "database unavailable", body = ErrorBody),
+    )
+)]
 pub async fn get_nodes_info(
     State(shared): State<Arc<Shared>>,
-    extract::Json(req): extract::Json<GeneralRequest>,
-) -> impl IntoResponse {
-    let GeneralRequest { limit, offset } = req;
-    let ids = shared
-        .db_handler
-        .get_init_keys(limit, offset)
-        .await
-        .unwrap_or(vec![]);
+    req: Result<extract::Json<GeneralRequest>, JsonRejection>,
+) -> Result<ApiResponse<NodesInfo>, ApiError> {
+    let extract::Json(req) = req?;
+    let cursor = match req.cursor.as_deref().map(Cursor::decode) {
+        Some(Some(cursor)) if cursor.sort_by != req.sort_by => {
+            return Err(ApiError::bad_request(
+                "invalid_cursor",
+                "cursor does not match sort_by",
+            ))
+        }
+        Some(None) => return Err(ApiError::bad_request("invalid_cursor", "invalid cursor")),
+        Some(cursor) => cursor,
+        None => None,
+    };
+    // anything but hex digits would leak LIKE wildcards into the query
+    if req
+        .id_prefix
+        .as_ref()
+        .is_some_and(|prefix| !prefix.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err(ApiError::bad_request(
+            "invalid_id_prefix",
+            "id_prefix must be hex",
+        ));
+    }
+    let clock = shared.clock.current()?;
+    let epoch_info = clock.epoch;
+    let mut round_id = (epoch_info - 1).to_string();
+    if clock.registration_open(&shared.config.network) {
+        round_id = epoch_info.to_string();
+    }
+    let db = &shared.db_handler;
+    let total = db.count_nodes(&req, epoch_info - 1, &round_id).await? as usize;
+    let limit = req.limit.clamp(0, shared.config.limits.max_page_size);
+    // one more than asked tells whether another page follows
+    let mut nodes = db
+        .get_nodes_page(&req, epoch_info - 1, &round_id, cursor.as_ref(), limit + 1)
+        .await?;
+    let next_cursor = if nodes.len() as i64 > limit {
+        nodes.truncate(limit as usize);
+        nodes
+            .last()
+            .map(|(key, _, atx)| Cursor::of(req.sort_by, key, atx).encode())
+    } else {
+        None
+    };
+    let mut partial = Partial::default();
+    let mut smeshers = partial.check(shared.db_handler.get_smesher_states().await);
     let mut result = vec![];
-    let epoch_info = shared.rpc_handler.get_epoch().unwrap().epochnum.number;
+    for (Key { id, num_units }, status, atx) in nodes {
+        let registerations = partial.check(
+            shared
+                .db_handler
+                .get_chain_registerations_by_id(id.clone(), round_id.clone())
+                .await,
+        );
+        let smesher = smeshers.remove(&id);
+        result.push(NodeInfo::new(
+            id,
+            num_units,
+            status,
+            registerations,
+            atx,
+            smesher,
+        ));
+    }
+    let nodes_info = NodesInfo {
+        total,
+        next_cursor,
+        data: result,
+    };
+    Ok(ApiResponse::new(nodes_info, partial.is_partial()))
+}
+
+/// the lowercase hex of a 32 byte id, or why it is not one
+pub fn validate_id(id: &str) -> Result<String, String> {
+    let bytes = hex::decode(id).map_err(|e| e.to_string())?;
+    if bytes.len() != 32 {
+        return Err(format!("expected 32 bytes, got {}", bytes.len()));
+    }
+    Ok(hex::encode(bytes))
+}
+
+#[utoipa::path(
+    post,
+    path = "/nodes_info/batch",
+    tag = "nodes",
+    request_body = BatchRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, body = BatchNodesInfoResponse),
+        (status = 304, description = "unchanged since the etag in If-None-Match"),
+        (status = 400, description = "too many ids", body = ErrorBody),
+        (status = 422, description = "malformed request body", body = ErrorBody),
+        (status = 503, description = "database unavailable", body = ErrorBody),
+    )
+)]
+pub async fn get_nodes_info_batch(
+    State(shared): State<Arc<Shared>>,
+    req: Result<extract::Json<BatchRequest>, JsonRejection>,
+) -> Result<ApiResponse<BatchNodesInfo>, ApiError> {
+    let extract::Json(req) = req?;
+    let max_ids = shared.config.limits.max_batch_ids;
+    if req.ids.len() > max_ids {
+        return Err(ApiError::bad_request(
+            "too_many_ids",
+            format!("at most {} ids per request", max_ids),
+        ));
+    }
+    let clock = shared.clock.current()?;
+    let epoch_info = clock.epoch;
     let mut round_id = (epoch_info - 1).to_string();
-    let current_layer = shared.rpc_handler.get_layer().unwrap().layernum.number;
-    if current_layer >= epoch_info * 4032 + 2760 {
+    if clock.registration_open(&shared.config.network) {
         round_id = epoch_info.to_string();
     }
-    for Key { id, num_units } in ids {
-        let registerations = shared
-            .db_handler
-            .get_chain_registerations_by_id(id.clone(), round_id.clone())
-            .await
-            .unwrap_or(vec![]);
-        let atx = shared
-            .db_handler
-            .get_atxs_by_id(id.clone(), epoch_info - 1)
-            .await
-            .unwrap_or(AtxInfo::default());
-        result.push(NodeInfo::new(id, num_units, registerations, atx));
+    let mut atxs: HashMap<String, AtxInfo> = shared
+        .db_handler
+        .get_atxs_by_epoch(epoch_info - 1)
+        .await?
+        .into_iter()
+        .map(|KeyAtx { id, atx }| (id, atx))
+        .collect();
+    let registered: HashSet<String> = shared
+        .db_handler
+        .get_registered_ids(round_id.clone())
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut partial = Partial::default();
+    let mut smeshers = partial.check(shared.db_handler.get_smesher_states().await);
+    let mut seen = HashSet::new();
+    let mut result = BatchNodesInfo {
+        found: vec![],
+        not_found: vec![],
+        invalid: vec![],
+    };
+    for id in req.ids {
+        let normalized = match validate_id(&id) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                result.invalid.push(InvalidId { id, error });
+                continue;
+            }
+        };
+        if !seen.insert(normalized.clone()) {
+            continue;
+        }
+        let Some(Key { id, num_units }) = shared.db_handler.get_init_key(&normalized).await? else {
+            result.not_found.push(normalized);
+            continue;
+        };
+        let atx = atxs.remove(&id);
+        let status = NodeStatus::of(atx.is_some(), registered.contains(&id));
+        let registerations = partial.check(
+            shared
+                .db_handler
+                .get_chain_registerations_by_id(id.clone(), round_id.clone())
+                .await,
+        );
+        let smesher = smeshers.remove(&id);
+        result.found.push(NodeInfo::new(
+            id,
+            num_units,
+            status,
+            registerations,
+            atx.unwrap_or_default(),
+            smesher,
+        ));
     }
-    let total = shared.db_handler.count_initialzed().await.unwrap_or(0);
-    Json(json!({"code": 200, "data": json!({"total": total, "data": result})})).into_response()
+    Ok(ApiResponse::new(result, partial.is_partial()))
 }
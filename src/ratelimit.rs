@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{auth::ApiKey, error::ApiError, metrics::METRICS, openapi::API_PREFIX};
+
+/// buckets kept before full ones, then the least recently used, are dropped
+pub const MAX_BUCKETS: usize = 10_000;
+
+/// bucket of failed authentications per ip, its burst can be set like a route's
+pub const AUTH_ROUTE: &str = "auth";
+
+/// `route=burst` override given on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteBurst {
+    pub route: String,
+    pub burst: u32,
+}
+
+impl FromStr for RouteBurst {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (route, burst) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected route=burst, got {}", s))?;
+        let burst = burst
+            .parse()
+            .map_err(|e| format!("invalid burst for {}: {}", route, e))?;
+        Ok(Self {
+            route: route.to_string(),
+            burst,
+        })
+    }
+}
+
+/// refill rate given on the command line, must be positive
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        Ok(_) => Err("rate must be positive".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant, per_second: f64, burst: f64) -> f64 {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        (self.tokens + elapsed * per_second).min(burst)
+    }
+}
+
+/// token buckets per client and route, refilled at a shared rate
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: u32,
+    routes: HashMap<String, u32>,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64, burst: u32, routes: Vec<RouteBurst>) -> Arc<Self> {
+        Arc::new(Self {
+            per_second,
+            burst,
+            routes: routes.into_iter().map(|r| (r.route, r.burst)).collect(),
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn burst(&self, route: &str) -> f64 {
+        self.routes.get(route).copied().unwrap_or(self.burst) as f64
+    }
+
+    /// takes a token, or tells how long until one is available
+    pub fn check(&self, client: &str, route: &str) -> Result<(), Duration> {
+        self.take(client, route, Instant::now(), true)
+    }
+
+    /// `check` at `now`, which must not go back in time
+    pub fn check_at(&self, client: &str, route: &str, now: Instant) -> Result<(), Duration> {
+        self.take(client, route, now, true)
+    }
+
+    /// tells how long until a token is available without taking one
+    pub fn wait_at(&self, client: &str, route: &str, now: Instant) -> Result<(), Duration> {
+        self.take(client, route, now, false)
+    }
+
+    fn take(&self, client: &str, route: &str, now: Instant, consume: bool) -> Result<(), Duration> {
+        let burst = self.burst(route);
+        let key = (client.to_string(), route.to_string());
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&key) {
+            self.prune(&mut buckets, now);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            last: now,
+        });
+        bucket.tokens = bucket.refilled(now, self.per_second, burst);
+        bucket.last = now;
+        if bucket.tokens >= 1.0 {
+            if consume {
+                bucket.tokens -= 1.0;
+            }
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / self.per_second,
+        ))
+    }
+
+    /// drops full buckets, then the least recently used down to half the capacity, so a prune
+    /// runs at most once every `MAX_BUCKETS / 2` new clients
+    fn prune(&self, buckets: &mut HashMap<(String, String), Bucket>, now: Instant) {
+        buckets.retain(|(_, route), bucket| {
+            let burst = self.burst(route);
+            bucket.refilled(now, self.per_second, burst) < burst
+        });
+        let keep = MAX_BUCKETS / 2;
+        if buckets.len() <= keep {
+            return;
+        }
+        let mut used: Vec<_> = buckets
+            .iter()
+            .map(|(key, bucket)| (bucket.last, key.clone()))
+            .collect();
+        let drop = used.len() - keep;
+        used.select_nth_unstable_by_key(drop, |(last, _)| *last);
+        for (_, key) in &used[..drop] {
+            buckets.remove(key);
+        }
+    }
+}
+
+/// forwarded client of a request from a unix socket, only a local proxy can connect there;
+/// `X-Real-IP` is set by that proxy, and of `X-Forwarded-For` only the rightmost entry is,
+/// a client can send anything in front of it
+fn forwarded_for(req: &Request) -> Option<String> {
+    let headers = req.headers();
+    let real_ip = headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok());
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .next_back()
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next());
+    real_ip
+        .or(forwarded)
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+        .map(str::to_string)
+}
+
+fn client_ip(connect_info: Option<ConnectInfo<SocketAddr>>, req: &Request) -> String {
+    match connect_info {
+        Some(ConnectInfo(addr)) => addr.ip().to_string(),
+        None => forwarded_for(req).unwrap_or_else(|| "unix".to_string()),
+    }
+}
+
+fn too_many_requests(route: &str, kind: &str, retry_after: Duration) -> Response {
+    METRICS.rate_limited.with_label_values(&[route, kind]).inc();
+    let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let mut resp = ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limited",
+        format!("too many requests, retry after {}s", secs),
+    )
+    .into_response();
+    resp.headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    resp
+}
+
+/// limits failed authentications by ip, runs before authentication so a client sending unknown
+/// keys is turned away before their lookup once its bucket is empty
+pub async fn limit_auth_failures(
+    State(limiter): State<Arc<RateLimiter>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let client = format!("ip:{}", client_ip(connect_info, &req));
+    if let Err(retry_after) = limiter.wait_at(&client, AUTH_ROUTE, Instant::now()) {
+        return too_many_requests(AUTH_ROUTE, "ip", retry_after);
+    }
+    let resp = next.run(req).await;
+    if resp.status() == StatusCode::UNAUTHORIZED {
+        let _ = limiter.check(&client, AUTH_ROUTE);
+    }
+    resp
+}
+
+/// limits api keys by key and anonymous clients by ip, runs after authentication
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    // a versioned route and its alias share one bucket
+    let route = match route.strip_prefix(API_PREFIX) {
+        Some(alias) if alias.starts_with('/') => alias.to_string(),
+        _ => route,
+    };
+    let (kind, client) = match req.extensions().get::<ApiKey>() {
+        Some(key) => ("key", format!("key:{}", key.id)),
+        None => ("ip", format!("ip:{}", client_ip(connect_info, &req))),
+    };
+    if let Err(retry_after) = limiter.check(&client, &route) {
+        return too_many_requests(&route, kind, retry_after);
+    }
+    next.run(req).await
+}
@@ -1,8 +1,236 @@
-use std::{fmt::Debug, time::Duration};
+use std::{
+    fmt::{self, Debug, Display},
+    future::Future,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::anyhow;
-use serde::{Deserialize, Serialize};
-use ureq::{Agent, AgentBuilder, Error, Response};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{
+    header::{HeaderValue, AUTHORIZATION},
+    Client,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use tokio::{sync::OnceCell, time::sleep};
+use tonic::transport::Uri;
+use utoipa::ToSchema;
+
+use crate::metrics::METRICS;
+
+pub mod error;
+pub mod failover;
+pub mod grpc;
+pub mod retry;
+
+pub use error::RpcError;
+pub use failover::{EndpointHealth, RpcHandler};
+use grpc::GrpcClient;
+use retry::{backoff, CircuitBreaker, BREAKER_COOLDOWN, BREAKER_THRESHOLD, RETRY_ATTEMPTS};
+
+/// per request, default of `limits.rpc_timeout_secs`
+pub const RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// node address, `grpc://host:port` or `grpcs://host:port` talks to the grpc services,
+/// anything else to the json gateway
+#[derive(Clone, PartialEq, Eq)]
+pub enum NodeEndpoint {
+    /// base url without a trailing slash
+    Gateway(String),
+    /// `https` when the channel uses tls
+    Grpc(Uri),
+}
+
+impl FromStr for NodeEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let grpc = match (s.strip_prefix("grpc://"), s.strip_prefix("grpcs://")) {
+            (Some(addr), _) => Some(format!("http://{}", addr)),
+            (_, Some(addr)) => Some(format!("https://{}", addr)),
+            _ => None,
+        };
+        if let Some(url) = grpc {
+            let uri = url
+                .trim_end_matches('/')
+                .parse::<Uri>()
+                .map_err(|e| format!("invalid grpc endpoint {}: {}", s, e))?;
+            return Ok(NodeEndpoint::Grpc(uri));
+        }
+        let url = if s.contains("://") {
+            s.to_string()
+        } else {
+            format!("http://{}", s)
+        };
+        reqwest::Url::parse(&url).map_err(|e| format!("invalid endpoint {}: {}", s, e))?;
+        Ok(NodeEndpoint::Gateway(url.trim_end_matches('/').to_string()))
+    }
+}
+
+impl NodeEndpoint {
+    /// host and port without scheme, path or credentials, safe to show on public routes
+    pub fn address(&self) -> String {
+        let (host, port) = match self {
+            NodeEndpoint::Gateway(url) => match reqwest::Url::parse(url) {
+                Ok(url) => (
+                    url.host_str().unwrap_or_default().to_string(),
+                    url.port_or_known_default(),
+                ),
+                Err(_) => return String::new(),
+            },
+            NodeEndpoint::Grpc(uri) => {
+                let default = if uri.scheme_str() == Some("https") {
+                    443
+                } else {
+                    80
+                };
+                (
+                    uri.host().unwrap_or_default().to_string(),
+                    Some(uri.port_u16().unwrap_or(default)),
+                )
+            }
+        };
+        match port {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        }
+    }
+
+    /// as configured, credentials included, only for sending requests
+    fn url(&self) -> String {
+        match self {
+            NodeEndpoint::Gateway(url) => url.clone(),
+            NodeEndpoint::Grpc(uri) => uri.to_string(),
+        }
+    }
+}
+
+/// the url without its `user:password@`, for logs
+impl Display for NodeEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeEndpoint::Gateway(url) => match reqwest::Url::parse(url) {
+                Ok(mut url) => {
+                    let _ = url.set_username("");
+                    let _ = url.set_password(None);
+                    write!(f, "{}", url.as_str().trim_end_matches('/'))
+                }
+                Err(_) => write!(f, "{}", self.address()),
+            },
+            NodeEndpoint::Grpc(uri) => {
+                let scheme = if uri.scheme_str() == Some("https") {
+                    "grpcs"
+                } else {
+                    "grpc"
+                };
+                let authority = uri.authority().map_or("", |a| a.as_str());
+                let host = authority
+                    .rsplit_once('@')
+                    .map_or(authority, |(_, host)| host);
+                write!(f, "{}://{}", scheme, host)
+            }
+        }
+    }
+}
+
+/// like `Display`, the config is logged at startup
+impl Debug for NodeEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variant = match self {
+            NodeEndpoint::Gateway(_) => "Gateway",
+            NodeEndpoint::Grpc(_) => "Grpc",
+        };
+        f.debug_tuple(variant).field(&self.to_string()).finish()
+    }
+}
+
+/// credentials sent with every node request, `bearer:<token>` or `basic:<user>:<password>`
+#[derive(Clone, PartialEq, Eq)]
+pub enum NodeAuth {
+    Bearer(String),
+    Basic { user: String, password: String },
+}
+
+impl NodeAuth {
+    pub fn header(&self) -> Result<HeaderValue, reqwest::header::InvalidHeaderValue> {
+        let value = match self {
+            NodeAuth::Bearer(token) => format!("Bearer {}", token),
+            NodeAuth::Basic { user, password } => {
+                format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{}:{}", user, password))
+                )
+            }
+        };
+        let mut value = HeaderValue::from_str(&value)?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+impl FromStr for NodeAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("bearer", token)) if !token.is_empty() => Ok(NodeAuth::Bearer(token.to_string())),
+            Some(("basic", credentials)) => match credentials.split_once(':') {
+                Some((user, password)) if !user.is_empty() => Ok(NodeAuth::Basic {
+                    user: user.to_string(),
+                    password: password.to_string(),
+                }),
+                _ => Err("expected basic:<user>:<password>".to_string()),
+            },
+            _ => Err("expected bearer:<token> or basic:<user>:<password>".to_string()),
+        }
+    }
+}
+
+/// keeps secrets out of the startup log
+impl Debug for NodeAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeAuth::Bearer(_) => write!(f, "Bearer(***)"),
+            NodeAuth::Basic { user, .. } => write!(f, "Basic({}:***)", user),
+        }
+    }
+}
+
+/// how every endpoint is talked to
+#[derive(Debug, Clone)]
+pub struct RpcOptions {
+    pub version: ApiVersion,
+    /// pem certificates trusted next to the webpki roots, for https and grpcs endpoints
+    pub ca: Option<Vec<u8>>,
+    pub auth: Option<NodeAuth>,
+    /// per request, retries take longer
+    pub timeout: Duration,
+    /// tries per request, including the first
+    pub retry_attempts: u32,
+    /// consecutive failed requests that open an endpoint's circuit
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    /// endpoints trailing the most advanced one by more layers than this are flagged
+    pub max_layer_divergence: i64,
+    pub agreement_interval: Duration,
+}
+
+impl Default for RpcOptions {
+    fn default() -> Self {
+        Self {
+            version: ApiVersion::default(),
+            ca: None,
+            auth: None,
+            timeout: RPC_TIMEOUT,
+            retry_attempts: RETRY_ATTEMPTS,
+            breaker_threshold: BREAKER_THRESHOLD,
+            breaker_cooldown: BREAKER_COOLDOWN,
+            max_layer_divergence: failover::MAX_LAYER_DIVERGENCE,
+            agreement_interval: failover::AGREEMENT_INTERVAL,
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Number {
@@ -19,45 +247,685 @@ pub struct LayerInfo {
     pub layernum: Number,
 }
 
+/// node api generation, `auto` asks the node on first use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiVersion {
+    #[default]
+    Auto,
+    V1,
+    V2alpha1,
+}
+
+/// protojson writes 64 bit integers as strings
+fn de_i64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Int {
+        Number(i64),
+        String(String),
+    }
+    match Int::deserialize(deserializer)? {
+        Int::Number(n) => Ok(n),
+        Int::String(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// protojson durations look like `300s`
+fn de_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.trim_end_matches('s')
+        .parse::<f64>()
+        .map(|secs| secs as i64)
+        .map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NetworkInfo {
+    pub layers_per_epoch: i64,
+    /// seconds
+    pub layer_duration: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct NodeState {
+    pub connected_peers: i64,
+    pub synced: bool,
+    pub synced_layer: i64,
+    pub top_layer: i64,
+    pub verified_layer: i64,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum PostSetupState {
+    #[default]
+    Unspecified,
+    NotStarted,
+    Prepared,
+    InProgress,
+    Complete,
+    Error,
+    Paused,
+}
+
+impl PostSetupState {
+    /// from the proto enum name, e.g. `STATE_IN_PROGRESS`
+    pub fn from_proto_name(name: &str) -> Self {
+        match name {
+            "STATE_NOT_STARTED" => PostSetupState::NotStarted,
+            "STATE_PREPARED" => PostSetupState::Prepared,
+            "STATE_IN_PROGRESS" => PostSetupState::InProgress,
+            "STATE_COMPLETE" => PostSetupState::Complete,
+            "STATE_ERROR" => PostSetupState::Error,
+            "STATE_PAUSED" => PostSetupState::Paused,
+            _ => PostSetupState::Unspecified,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PostSetup {
+    pub state: PostSetupState,
+    pub num_labels_written: i64,
+    pub num_units: i64,
+}
+
+/// one identity of a node, the node reports a single post setup for all of them
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Smesher {
+    pub id: String,
+    pub smeshing: bool,
+    pub post: PostSetup,
+}
+
+/// atx as listed by the v2alpha1 activation service, ids hex encoded like the rest of poolstats
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Activation {
+    pub id: String,
+    pub smesher_id: String,
+    pub publish_epoch: i64,
+    pub coinbase: String,
+    pub num_units: i64,
+    pub weight: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct V1NodeStatus {
+    #[serde(deserialize_with = "de_i64")]
+    connected_peers: i64,
+    is_synced: bool,
+    synced_layer: Option<Number>,
+    top_layer: Option<Number>,
+    verified_layer: Option<Number>,
+}
+
+#[derive(Debug, Deserialize)]
+struct V1NodeStatusResponse {
+    status: V1NodeStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct V1Value {
+    #[serde(deserialize_with = "de_i64")]
+    value: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct V1LayerDuration {
+    duration: V1Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct V1EpochNumLayers {
+    numlayers: Number,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct V1IsSmeshing {
+    is_smeshing: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct V1SmesherIds {
+    public_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct V1AccountId {
+    address: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V1Coinbase {
+    account_id: V1AccountId,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct V1PostSetupOpts {
+    num_units: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct V1PostSetupStatus {
+    state: String,
+    #[serde(deserialize_with = "de_i64")]
+    num_labels_written: i64,
+    opts: Option<V1PostSetupOpts>,
+}
+
+#[derive(Debug, Deserialize)]
+struct V1PostSetupStatusResponse {
+    status: V1PostSetupStatus,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V2NetworkInfo {
+    #[serde(deserialize_with = "de_duration")]
+    layer_duration: i64,
+    layers_per_epoch: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct V2NodeStatus {
+    #[serde(deserialize_with = "de_i64")]
+    connected_peers: i64,
+    status: String,
+    latest_layer: i64,
+    applied_layer: i64,
+    processed_layer: i64,
+    current_layer: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct V2ActivationsCount {
+    count: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct V2Activation {
+    id: String,
+    smesher_id: String,
+    publish_epoch: i64,
+    coinbase: String,
+    num_units: i64,
+    #[serde(deserialize_with = "de_i64")]
+    weight: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct V2ActivationList {
+    activations: Vec<V2Activation>,
+}
+
+fn base64_to_hex(id: &str) -> Result<String, RpcError> {
+    Ok(hex::encode(STANDARD.decode(id)?))
+}
+
+/// one node endpoint, `RpcHandler` spreads requests over several of them
 #[derive(Debug, Clone)]
-pub struct RpcHandler {
-    pub endpoint: String,
-    pub agent: Agent,
+pub struct NodeClient {
+    pub endpoint: NodeEndpoint,
+    pub client: Client,
+    /// as configured, `resolved` holds the outcome of negotiating `auto`
+    pub version: ApiVersion,
+    authorization: Option<HeaderValue>,
+    grpc: Option<GrpcClient>,
+    retry_attempts: u32,
+    breaker: Arc<CircuitBreaker>,
+    resolved: Arc<OnceCell<ApiVersion>>,
+    network: Arc<OnceCell<NetworkInfo>>,
 }
 
-impl RpcHandler {
-    pub fn new(endpoint: String) -> Self {
-        Self {
+impl NodeClient {
+    /// grpc channels connect lazily, so this has to run inside the tokio runtime
+    pub fn new(endpoint: NodeEndpoint, options: &RpcOptions) -> anyhow::Result<Self> {
+        let authorization = options.auth.as_ref().map(NodeAuth::header).transpose()?;
+        let grpc = match &endpoint {
+            NodeEndpoint::Grpc(uri) => Some(GrpcClient::new(
+                uri.clone(),
+                options.timeout,
+                options.ca.as_deref(),
+                authorization.clone(),
+            )?),
+            NodeEndpoint::Gateway(_) => None,
+        };
+        let mut client = Client::builder().timeout(options.timeout);
+        if let Some(ca) = &options.ca {
+            for cert in reqwest::Certificate::from_pem_bundle(ca)? {
+                client = client.add_root_certificate(cert);
+            }
+        }
+        Ok(Self {
             endpoint,
-            agent: AgentBuilder::new()
-                .timeout_read(Duration::from_secs(5))
-                .timeout_write(Duration::from_secs(5))
-                .build(),
+            client: client.build()?,
+            version: options.version,
+            authorization,
+            grpc,
+            retry_attempts: options.retry_attempts,
+            breaker: Arc::new(CircuitBreaker::new(
+                options.breaker_threshold,
+                options.breaker_cooldown,
+            )),
+            resolved: Arc::new(OnceCell::new()),
+            network: Arc::new(OnceCell::new()),
+        })
+    }
+
+    /// api spoken with the node, a failed negotiation is retried on the next call
+    pub async fn api_version(&self) -> Result<ApiVersion, RpcError> {
+        if self.grpc.is_some() {
+            // only the v1 services are vendored
+            return match self.version {
+                ApiVersion::V2alpha1 => Err(RpcError::Unsupported(
+                    "v2alpha1 is not served over grpc".to_string(),
+                )),
+                _ => Ok(ApiVersion::V1),
+            };
+        }
+        if self.version != ApiVersion::Auto {
+            return Ok(self.version);
+        }
+        self.resolved
+            .get_or_try_init(|| self.negotiate())
+            .await
+            .copied()
+    }
+
+    /// an unreachable node is reported as such rather than as speaking neither api
+    async fn negotiate(&self) -> Result<ApiVersion, RpcError> {
+        let version = match self.v2_network_info().await {
+            Ok(_) => ApiVersion::V2alpha1,
+            Err(e) if e.is_transient() => return Err(e),
+            Err(_) => match self.v1_epoch().await {
+                Ok(_) => ApiVersion::V1,
+                Err(e) if e.is_transient() => return Err(e),
+                Err(_) => {
+                    return Err(RpcError::Unsupported(format!(
+                        "node at {} answers neither v2alpha1 nor v1",
+                        self.endpoint
+                    )))
+                }
+            },
+        };
+        log::info!("node at {} speaks {:?}", self.endpoint, version);
+        Ok(version)
+    }
+
+    pub async fn get_epoch(&self) -> Result<EpochInfo, RpcError> {
+        match self.api_version().await? {
+            ApiVersion::V2alpha1 => {
+                let layer = self.v2_node_status().await?.current_layer;
+                let network = self.network_info().await?;
+                Ok(EpochInfo {
+                    epochnum: Number {
+                        number: layer / network.layers_per_epoch,
+                    },
+                })
+            }
+            _ => self.v1_epoch().await,
+        }
+    }
+
+    pub async fn get_layer(&self) -> Result<LayerInfo, RpcError> {
+        match self.api_version().await? {
+            ApiVersion::V2alpha1 => Ok(LayerInfo {
+                layernum: Number {
+                    number: self.v2_node_status().await?.current_layer,
+                },
+            }),
+            _ => self.v1_layer().await,
         }
     }
 
-    pub fn get_epoch(&self) -> anyhow::Result<EpochInfo> {
-        let path = format!("http://{}/v1/mesh/currentepoch", self.endpoint);
-        let resp = self.agent.clone().post(&path).call();
-        handle_response(resp)
+    /// constant for a network, fetched once
+    pub async fn network_info(&self) -> Result<NetworkInfo, RpcError> {
+        let version = self.api_version().await?;
+        self.network
+            .get_or_try_init(|| async {
+                match version {
+                    ApiVersion::V2alpha1 => self.v2_network_info().await,
+                    _ => self.v1_network_info().await,
+                }
+            })
+            .await
+            .copied()
     }
 
-    pub fn get_layer(&self) -> anyhow::Result<LayerInfo> {
-        let path = format!("http://{}/v1/mesh/currentlayer", self.endpoint);
-        let resp = self.agent.clone().post(&path).call();
-        handle_response(resp)
+    pub async fn node_status(&self) -> Result<NodeState, RpcError> {
+        match self.api_version().await? {
+            ApiVersion::V2alpha1 => {
+                let status = self.v2_node_status().await?;
+                Ok(NodeState {
+                    connected_peers: status.connected_peers,
+                    synced: status.status == "SYNC_STATUS_SYNCED",
+                    synced_layer: status.processed_layer,
+                    top_layer: status.latest_layer,
+                    verified_layer: status.applied_layer,
+                })
+            }
+            _ => {
+                if let Some(grpc) = &self.grpc {
+                    return self.request("node_status", || grpc.node_status()).await;
+                }
+                let resp: V1NodeStatusResponse = self
+                    .call("node_status", "/v1/node/status", json!({}))
+                    .await?;
+                let layer = |layer: Option<Number>| layer.map_or(0, |layer| layer.number);
+                Ok(NodeState {
+                    connected_peers: resp.status.connected_peers,
+                    synced: resp.status.is_synced,
+                    synced_layer: layer(resp.status.synced_layer),
+                    top_layer: layer(resp.status.top_layer),
+                    verified_layer: layer(resp.status.verified_layer),
+                })
+            }
+        }
+    }
+
+    /// the smesher service only exists in v1, nodes serving v2alpha1 keep serving it
+    pub async fn is_smeshing(&self) -> Result<bool, RpcError> {
+        if let Some(grpc) = &self.grpc {
+            return self.request("is_smeshing", || grpc.is_smeshing()).await;
+        }
+        let resp: V1IsSmeshing = self
+            .call("is_smeshing", "/v1/smesher/issmeshing", json!({}))
+            .await?;
+        Ok(resp.is_smeshing)
+    }
+
+    /// hex encoded identities the node smeshes with
+    pub async fn smesher_ids(&self) -> Result<Vec<String>, RpcError> {
+        if let Some(grpc) = &self.grpc {
+            return self.request("smesher_ids", || grpc.smesher_ids()).await;
+        }
+        let resp: V1SmesherIds = self
+            .call("smesher_ids", "/v1/smesher/smesherids", json!({}))
+            .await?;
+        resp.public_keys
+            .iter()
+            .map(|key| base64_to_hex(key))
+            .collect()
+    }
+
+    pub async fn coinbase(&self) -> Result<String, RpcError> {
+        if let Some(grpc) = &self.grpc {
+            return self.request("coinbase", || grpc.coinbase()).await;
+        }
+        let resp: V1Coinbase = self
+            .call("coinbase", "/v1/smesher/coinbase", json!({}))
+            .await?;
+        Ok(resp.account_id.address)
+    }
+
+    pub async fn post_setup_status(&self) -> Result<PostSetup, RpcError> {
+        if let Some(grpc) = &self.grpc {
+            return self
+                .request("post_setup_status", || grpc.post_setup_status())
+                .await;
+        }
+        let resp: V1PostSetupStatusResponse = self
+            .call(
+                "post_setup_status",
+                "/v1/smesher/postsetupstatus",
+                json!({}),
+            )
+            .await?;
+        Ok(PostSetup {
+            state: PostSetupState::from_proto_name(&resp.status.state),
+            num_labels_written: resp.status.num_labels_written,
+            num_units: resp.status.opts.map_or(0, |opts| opts.num_units),
+        })
+    }
+
+    pub async fn smeshers(&self) -> Result<Vec<Smesher>, RpcError> {
+        let ids = self.smesher_ids().await?;
+        let smeshing = self.is_smeshing().await?;
+        let post = self.post_setup_status().await?;
+        Ok(ids
+            .into_iter()
+            .map(|id| Smesher {
+                id,
+                smeshing,
+                post: post.clone(),
+            })
+            .collect())
+    }
+
+    /// atxs published in `epoch`, only served by v2alpha1
+    pub async fn activations_count(&self, epoch: i64) -> Result<i64, RpcError> {
+        self.require_v2("activations_count").await?;
+        let resp: V2ActivationsCount = self
+            .call(
+                "activations_count",
+                "/spacemesh.v2alpha1.ActivationService/ActivationsCount",
+                json!({ "epoch": epoch }),
+            )
+            .await?;
+        Ok(resp.count)
+    }
+
+    /// atxs of the given hex encoded smesher ids published in `epoch`, only served by v2alpha1
+    pub async fn activations(
+        &self,
+        epoch: i64,
+        smesher_ids: &[String],
+    ) -> Result<Vec<Activation>, RpcError> {
+        self.require_v2("activations").await?;
+        let ids = smesher_ids
+            .iter()
+            .map(|id| {
+                hex::decode(id)
+                    .map(|id| STANDARD.encode(id))
+                    .map_err(|e| RpcError::Unsupported(format!("smesher id {}: {}", id, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let resp: V2ActivationList = self
+            .call(
+                "activations",
+                "/spacemesh.v2alpha1.ActivationService/List",
+                json!({
+                    "smesherId": ids,
+                    "startEpoch": epoch,
+                    "endEpoch": epoch,
+                    "limit": ids.len().max(1),
+                }),
+            )
+            .await?;
+        resp.activations
+            .into_iter()
+            .map(|atx| {
+                Ok(Activation {
+                    id: base64_to_hex(&atx.id)?,
+                    smesher_id: base64_to_hex(&atx.smesher_id)?,
+                    publish_epoch: atx.publish_epoch,
+                    coinbase: atx.coinbase,
+                    num_units: atx.num_units,
+                    weight: atx.weight,
+                })
+            })
+            .collect()
+    }
+
+    async fn require_v2(&self, method: &str) -> Result<(), RpcError> {
+        match self.api_version().await? {
+            ApiVersion::V2alpha1 => Ok(()),
+            version => Err(RpcError::Unsupported(format!(
+                "{} is not served by the {:?} api",
+                method, version
+            ))),
+        }
+    }
+
+    async fn v1_epoch(&self) -> Result<EpochInfo, RpcError> {
+        if let Some(grpc) = &self.grpc {
+            let number = self
+                .request("currentepoch", || grpc.current_epoch())
+                .await?;
+            return Ok(EpochInfo {
+                epochnum: Number { number },
+            });
+        }
+        self.call("currentepoch", "/v1/mesh/currentepoch", json!({}))
+            .await
+    }
+
+    async fn v1_layer(&self) -> Result<LayerInfo, RpcError> {
+        if let Some(grpc) = &self.grpc {
+            let number = self
+                .request("currentlayer", || grpc.current_layer())
+                .await?;
+            return Ok(LayerInfo {
+                layernum: Number { number },
+            });
+        }
+        self.call("currentlayer", "/v1/mesh/currentlayer", json!({}))
+            .await
+    }
+
+    async fn v1_network_info(&self) -> Result<NetworkInfo, RpcError> {
+        if let Some(grpc) = &self.grpc {
+            return self.request("network_info", || grpc.network_info()).await;
+        }
+        let layers: V1EpochNumLayers = self
+            .call("epochnumlayers", "/v1/mesh/epochnumlayers", json!({}))
+            .await?;
+        let duration: V1LayerDuration = self
+            .call("layerduration", "/v1/mesh/layerduration", json!({}))
+            .await?;
+        Ok(NetworkInfo {
+            layers_per_epoch: layers.numlayers.number,
+            layer_duration: duration.duration.value,
+        })
+    }
+
+    async fn v2_network_info(&self) -> Result<NetworkInfo, RpcError> {
+        let info: V2NetworkInfo = self
+            .call(
+                "network_info",
+                "/spacemesh.v2alpha1.NetworkService/Info",
+                json!({}),
+            )
+            .await?;
+        if info.layers_per_epoch <= 0 {
+            return Err(RpcError::Decode(format!(
+                "node reported {} layers per epoch",
+                info.layers_per_epoch
+            )));
+        }
+        Ok(NetworkInfo {
+            layers_per_epoch: info.layers_per_epoch,
+            layer_duration: info.layer_duration,
+        })
+    }
+
+    async fn v2_node_status(&self) -> Result<V2NodeStatus, RpcError> {
+        self.call(
+            "node_status",
+            "/spacemesh.v2alpha1.NodeService/Status",
+            json!({}),
+        )
+        .await
+    }
+
+    /// sends through the circuit breaker, retrying transient failures with jittered backoff;
+    /// every attempt is timed and failed ones are counted
+    async fn request<T, F, Fut>(&self, method: &str, send: F) -> Result<T, RpcError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, RpcError>>,
+    {
+        if !self.breaker.allow() {
+            return Err(RpcError::CircuitOpen {
+                endpoint: self.endpoint.address(),
+            });
+        }
+        let mut attempt = 0;
+        let result = loop {
+            let timer = METRICS
+                .rpc_latency
+                .with_label_values(&[method])
+                .start_timer();
+            let result = send().await;
+            timer.observe_duration();
+            match result {
+                Err(e) => {
+                    METRICS.rpc_errors.with_label_values(&[method]).inc();
+                    attempt += 1;
+                    if !e.is_transient() || attempt >= self.retry_attempts {
+                        break Err(e);
+                    }
+                    METRICS.rpc_retries.with_label_values(&[method]).inc();
+                    log::debug!("{} on {} failed, retrying: {}", method, self.endpoint, e);
+                    sleep(backoff(attempt - 1)).await;
+                }
+                Ok(resp) => break Ok(resp),
+            }
+        };
+        let endpoint = self.endpoint.address();
+        // an answer the node did not like still means it is up
+        if !result.as_ref().is_err_and(RpcError::is_transient) {
+            self.breaker.success();
+        } else if self.breaker.failure() {
+            log::warn!(
+                "{} failed {} times in a row, pausing requests for {}s",
+                endpoint,
+                self.breaker.threshold,
+                self.breaker.cooldown.as_secs()
+            );
+        }
+        METRICS
+            .rpc_circuit_open
+            .with_label_values(&[&endpoint])
+            .set(self.breaker.is_open() as i64);
+        result
+    }
+
+    async fn call<S: Debug + for<'a> Deserialize<'a>>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Value,
+    ) -> Result<S, RpcError> {
+        let url = format!("{}{}", self.endpoint.url(), path);
+        self.request(method, || async {
+            let mut request = self.client.post(&url).json(&body);
+            if let Some(authorization) = &self.authorization {
+                request = request.header(AUTHORIZATION, authorization.clone());
+            }
+            handle_response(request.send().await).await
+        })
+        .await
     }
 }
 
-pub fn handle_response<S: Debug + for<'a> Deserialize<'a>>(
-    resp: Result<Response, Error>,
-) -> anyhow::Result<S> {
-    let res = match resp {
-        Ok(response) => match response.into_json::<S>() {
-            Ok(data) => Ok(data),
-            Err(e) => Err(anyhow!(e.to_string())),
-        },
-        Err(e) => Err(anyhow!(e.to_string())),
-    };
-    res
+pub async fn handle_response<S: Debug + for<'a> Deserialize<'a>>(
+    resp: Result<reqwest::Response, reqwest::Error>,
+) -> Result<S, RpcError> {
+    let response = resp?;
+    let status = response.status();
+    if !status.is_success() {
+        // the gateway explains itself in the body
+        let message = response.text().await.unwrap_or_default();
+        return Err(RpcError::Status {
+            status: status.as_u16(),
+            message: message.chars().take(200).collect(),
+        });
+    }
+    let body = response.bytes().await?;
+    serde_json::from_slice(&body).map_err(|e| RpcError::Decode(e.to_string()))
 }
@@ -0,0 +1,124 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+use reqwest::StatusCode;
+use tonic::Code;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    /// no answer within the rpc timeout
+    Timeout,
+    /// the node could not be reached
+    Connect(String),
+    /// the json gateway answered with an error status
+    Status {
+        status: u16,
+        message: String,
+    },
+    /// the grpc service answered with an error status
+    Grpc {
+        code: Code,
+        message: String,
+    },
+    /// the answer could not be read
+    Decode(String),
+    /// the node does not serve the request with the api it speaks
+    Unsupported(String),
+    /// recent requests to the endpoint failed, it is left alone for a while
+    CircuitOpen {
+        endpoint: String,
+    },
+    NoEndpoints,
+}
+
+impl RpcError {
+    /// worth retrying: the node may answer the same request a moment later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RpcError::Timeout | RpcError::Connect(_) => true,
+            RpcError::Status { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS.as_u16() || *status >= 500
+            }
+            RpcError::Grpc { code, .. } => {
+                matches!(
+                    code,
+                    Code::Unavailable | Code::ResourceExhausted | Code::Aborted
+                )
+            }
+            RpcError::Decode(_)
+            | RpcError::Unsupported(_)
+            | RpcError::CircuitOpen { .. }
+            | RpcError::NoEndpoints => false,
+        }
+    }
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Timeout => write!(f, "node rpc timed out"),
+            RpcError::Connect(e) => write!(f, "node unreachable: {}", e),
+            RpcError::Status { status, message } => {
+                write!(f, "node answered {}: {}", status, message)
+            }
+            RpcError::Grpc { code, message } => {
+                write!(f, "node answered {:?}: {}", code, message)
+            }
+            RpcError::Decode(e) => write!(f, "malformed node answer: {}", e),
+            RpcError::Unsupported(e) => write!(f, "{}", e),
+            RpcError::CircuitOpen { endpoint } => {
+                write!(f, "circuit open for {}, recent requests failed", endpoint)
+            }
+            RpcError::NoEndpoints => write!(f, "no node endpoint configured"),
+        }
+    }
+}
+
+impl Error for RpcError {}
+
+impl From<reqwest::Error> for RpcError {
+    /// the url is left out, it may carry credentials and the message ends up on `/sync_status`
+    fn from(e: reqwest::Error) -> Self {
+        let e = e.without_url();
+        if e.is_timeout() {
+            RpcError::Timeout
+        } else if let Some(status) = e.status() {
+            RpcError::Status {
+                status: status.as_u16(),
+                message: e.to_string(),
+            }
+        } else if e.is_decode() || e.is_body() {
+            RpcError::Decode(e.to_string())
+        } else {
+            RpcError::Connect(e.to_string())
+        }
+    }
+}
+
+impl From<tonic::Status> for RpcError {
+    fn from(status: tonic::Status) -> Self {
+        match status.code() {
+            // the channel timeout cancels the call
+            Code::DeadlineExceeded | Code::Cancelled => RpcError::Timeout,
+            Code::Unavailable => RpcError::Connect(status.message().to_string()),
+            code => RpcError::Grpc {
+                code,
+                message: status.message().to_string(),
+            },
+        }
+    }
+}
+
+impl From<base64::DecodeError> for RpcError {
+    fn from(e: base64::DecodeError) -> Self {
+        RpcError::Decode(e.to_string())
+    }
+}
+
+impl From<hex::FromHexError> for RpcError {
+    fn from(e: hex::FromHexError) -> Self {
+        RpcError::Decode(e.to_string())
+    }
+}
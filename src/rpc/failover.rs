@@ -0,0 +1,338 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use utoipa::ToSchema;
+
+use super::{
+    Activation, ApiVersion, EpochInfo, LayerInfo, NetworkInfo, NodeClient, NodeEndpoint, NodeState,
+    PostSetup, RpcError, RpcOptions, Smesher,
+};
+
+/// endpoints trailing the most advanced one by more layers than this are flagged,
+/// default of `alerts.max_layer_divergence`
+pub const MAX_LAYER_DIVERGENCE: i64 = 3;
+/// how often every endpoint is asked for its epoch and layer
+pub const AGREEMENT_INTERVAL: Duration = Duration::from_secs(30);
+/// weight of the newest sample in the latency and error moving averages
+const EWMA_ALPHA: f64 = 0.2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct EndpointHealth {
+    /// host and port, see `NodeEndpoint::address`
+    pub endpoint: String,
+    /// moving average of the request latency in milliseconds
+    pub latency_ms: f64,
+    /// moving average of failed requests, 0 to 1
+    pub error_rate: f64,
+    pub requests: u64,
+    pub failures: u64,
+    pub last_error: Option<String>,
+    /// as of the last agreement check
+    pub epoch: Option<i64>,
+    pub layer: Option<i64>,
+    /// epoch or layer disagree with the most advanced endpoint
+    pub diverged: bool,
+}
+
+impl EndpointHealth {
+    /// lower is better, diverged endpoints are only used when nothing else answers;
+    /// an endpoint failing every request ranks like one answering at `penalty_ms`
+    fn score(&self, penalty_ms: f64) -> f64 {
+        let score = self.latency_ms + self.error_rate * penalty_ms;
+        if self.diverged {
+            score + 2.0 * penalty_ms
+        } else {
+            score
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration, error: Option<&RpcError>) {
+        let latency_ms = elapsed.as_secs_f64() * 1000.0;
+        let failed = if error.is_some() { 1.0 } else { 0.0 };
+        if self.requests == 0 {
+            self.latency_ms = latency_ms;
+            self.error_rate = failed;
+        } else {
+            self.latency_ms += EWMA_ALPHA * (latency_ms - self.latency_ms);
+            self.error_rate += EWMA_ALPHA * (failed - self.error_rate);
+        }
+        self.requests += 1;
+        if let Some(e) = error {
+            self.failures += 1;
+            self.last_error = Some(e.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Endpoint {
+    client: NodeClient,
+    health: Arc<Mutex<EndpointHealth>>,
+}
+
+impl Endpoint {
+    async fn request<T, F, Fut>(&self, f: &F) -> Result<T, RpcError>
+    where
+        F: Fn(NodeClient) -> Fut,
+        Fut: Future<Output = Result<T, RpcError>>,
+    {
+        let started = Instant::now();
+        let result = f(self.client.clone()).await;
+        self.health
+            .lock()
+            .unwrap()
+            .record(started.elapsed(), result.as_ref().err());
+        result
+    }
+}
+
+/// routes every request to the healthiest endpoint and fails over to the next on errors
+#[derive(Debug, Clone)]
+pub struct RpcHandler {
+    endpoints: Vec<Endpoint>,
+    /// the request timeout in milliseconds
+    penalty_ms: f64,
+    max_layer_divergence: i64,
+    agreement_interval: Duration,
+}
+
+impl RpcHandler {
+    /// plain endpoints without credentials
+    pub fn new(endpoints: Vec<NodeEndpoint>, version: ApiVersion) -> anyhow::Result<Self> {
+        Self::with_options(
+            endpoints,
+            RpcOptions {
+                version,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// grpc channels connect lazily, so this has to run inside the tokio runtime
+    pub fn with_options(endpoints: Vec<NodeEndpoint>, options: RpcOptions) -> anyhow::Result<Self> {
+        let endpoints = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                Ok(Endpoint {
+                    health: Arc::new(Mutex::new(EndpointHealth {
+                        endpoint: endpoint.address(),
+                        ..Default::default()
+                    })),
+                    client: NodeClient::new(endpoint, &options)?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            endpoints,
+            penalty_ms: options.timeout.as_millis() as f64,
+            max_layer_divergence: options.max_layer_divergence,
+            agreement_interval: options.agreement_interval,
+        })
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.health.lock().unwrap().clone())
+            .collect()
+    }
+
+    /// endpoints by score, configuration order breaks ties
+    fn ranked(&self) -> Vec<&Endpoint> {
+        let mut ranked: Vec<_> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let score = endpoint.health.lock().unwrap().score(self.penalty_ms);
+                (score, endpoint)
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ranked.into_iter().map(|(_, endpoint)| endpoint).collect()
+    }
+
+    async fn route<T, F, Fut>(&self, f: F) -> Result<T, RpcError>
+    where
+        F: Fn(NodeClient) -> Fut,
+        Fut: Future<Output = Result<T, RpcError>>,
+    {
+        let mut last = None;
+        for endpoint in self.ranked() {
+            match endpoint.request(&f).await {
+                Ok(resp) => return Ok(resp),
+                Err(e) => {
+                    if self.endpoints.len() > 1 {
+                        log::warn!(
+                            "node {} failed, failing over: {:#}",
+                            endpoint.client.endpoint.address(),
+                            e
+                        );
+                    }
+                    last = Some(e);
+                }
+            }
+        }
+        Err(last.unwrap_or(RpcError::NoEndpoints))
+    }
+
+    /// asks every endpoint for its epoch and layer and flags the ones behind the most advanced
+    pub async fn check_agreement(&self) -> Vec<EndpointHealth> {
+        let positions = join_all(self.endpoints.iter().map(|endpoint| async move {
+            let epoch = endpoint
+                .request(&|client| async move { client.get_epoch().await })
+                .await;
+            let layer = endpoint
+                .request(&|client| async move { client.get_layer().await })
+                .await;
+            match (epoch, layer) {
+                (Ok(epoch), Ok(layer)) => Some((epoch.epochnum.number, layer.layernum.number)),
+                _ => None,
+            }
+        }))
+        .await;
+        let leader = positions
+            .iter()
+            .flatten()
+            .max_by_key(|(_, layer)| *layer)
+            .copied();
+        for (endpoint, position) in self.endpoints.iter().zip(positions) {
+            let mut health = endpoint.health.lock().unwrap();
+            let diverged = match (leader, position) {
+                (Some((leader_epoch, leader_layer)), Some((epoch, layer))) => {
+                    epoch != leader_epoch || leader_layer - layer > self.max_layer_divergence
+                }
+                _ => false,
+            };
+            if diverged && !health.diverged {
+                let (epoch, layer) = position.unwrap_or_default();
+                let (leader_epoch, leader_layer) = leader.unwrap_or_default();
+                log::warn!(
+                    "node {} is at epoch {} layer {}, others are at epoch {} layer {}",
+                    health.endpoint,
+                    epoch,
+                    layer,
+                    leader_epoch,
+                    leader_layer
+                );
+            } else if !diverged && health.diverged && position.is_some() {
+                log::info!("node {} agrees with the others again", health.endpoint);
+            }
+            if let Some((epoch, layer)) = position {
+                health.epoch = Some(epoch);
+                health.layer = Some(layer);
+                health.diverged = diverged;
+            }
+        }
+        self.health()
+    }
+
+    /// keeps scores of idle endpoints fresh, so failover has somewhere healthy to go
+    pub async fn watch(self) {
+        loop {
+            self.check_agreement().await;
+            sleep(self.agreement_interval).await;
+        }
+    }
+
+    pub async fn api_version(&self) -> Result<ApiVersion, RpcError> {
+        self.route(|client| async move { client.api_version().await })
+            .await
+    }
+
+    pub async fn get_epoch(&self) -> Result<EpochInfo, RpcError> {
+        self.route(|client| async move { client.get_epoch().await })
+            .await
+    }
+
+    pub async fn get_layer(&self) -> Result<LayerInfo, RpcError> {
+        self.route(|client| async move { client.get_layer().await })
+            .await
+    }
+
+    pub async fn network_info(&self) -> Result<NetworkInfo, RpcError> {
+        self.route(|client| async move { client.network_info().await })
+            .await
+    }
+
+    pub async fn node_status(&self) -> Result<NodeState, RpcError> {
+        self.route(|client| async move { client.node_status().await })
+            .await
+    }
+
+    pub async fn is_smeshing(&self) -> Result<bool, RpcError> {
+        self.route(|client| async move { client.is_smeshing().await })
+            .await
+    }
+
+    pub async fn smesher_ids(&self) -> Result<Vec<String>, RpcError> {
+        self.route(|client| async move { client.smesher_ids().await })
+            .await
+    }
+
+    pub async fn coinbase(&self) -> Result<String, RpcError> {
+        self.route(|client| async move { client.coinbase().await })
+            .await
+    }
+
+    pub async fn post_setup_status(&self) -> Result<PostSetup, RpcError> {
+        self.route(|client| async move { client.post_setup_status().await })
+            .await
+    }
+
+    /// identities of every endpoint rather than the healthiest by the address of the endpoint,
+    /// each node smeshes its own; endpoints that did not answer are left out, fails only when
+    /// none answers
+    pub async fn smeshers(&self) -> Result<BTreeMap<String, Vec<Smesher>>, RpcError> {
+        let reports = join_all(self.endpoints.iter().map(|endpoint| async move {
+            let result = endpoint
+                .request(&|client| async move { client.smeshers().await })
+                .await;
+            (endpoint, result)
+        }))
+        .await;
+        let mut smeshers = BTreeMap::new();
+        let mut answered = false;
+        let mut last = None;
+        for (endpoint, result) in reports {
+            match result {
+                Ok(reported) => {
+                    answered = true;
+                    smeshers.insert(endpoint.client.endpoint.address(), reported);
+                }
+                Err(e) => {
+                    log::warn!(
+                        "node {} did not report its smeshers: {:#}",
+                        endpoint.client.endpoint.address(),
+                        e
+                    );
+                    last = Some(e);
+                }
+            }
+        }
+        match last {
+            Some(e) if !answered => Err(e),
+            _ => Ok(smeshers),
+        }
+    }
+
+    pub async fn activations_count(&self, epoch: i64) -> Result<i64, RpcError> {
+        self.route(|client| async move { client.activations_count(epoch).await })
+            .await
+    }
+
+    pub async fn activations(
+        &self,
+        epoch: i64,
+        smesher_ids: &[String],
+    ) -> Result<Vec<Activation>, RpcError> {
+        self.route(|client| async move { client.activations(epoch, smesher_ids).await })
+            .await
+    }
+}
@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use reqwest::header::HeaderValue;
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    transport::{Certificate, Channel, ClientTlsConfig, Uri},
+    Request,
+};
+
+use super::{NetworkInfo, NodeState, PostSetup, PostSetupState, RpcError};
+use proto::{
+    mesh_service_client::MeshServiceClient, node_service_client::NodeServiceClient,
+    smesher_service_client::SmesherServiceClient, CurrentEpochRequest, CurrentLayerRequest,
+    EpochNumLayersRequest, LayerDurationRequest, StatusRequest,
+};
+
+/// generated from the vendored protos in `proto/`
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("spacemesh.v1");
+}
+
+/// v1 mesh, node and smesher services over one lazily connected channel
+#[derive(Debug, Clone)]
+pub struct GrpcClient {
+    channel: Channel,
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+fn missing(field: &str) -> RpcError {
+    RpcError::Decode(format!("node sent no {}", field))
+}
+
+impl GrpcClient {
+    /// `ca` is trusted on top of the webpki roots for https endpoints
+    pub fn new(
+        uri: Uri,
+        timeout: Duration,
+        ca: Option<&[u8]>,
+        authorization: Option<HeaderValue>,
+    ) -> anyhow::Result<Self> {
+        let tls = uri.scheme_str() == Some("https");
+        let mut builder = Channel::builder(uri)
+            .connect_timeout(timeout)
+            .timeout(timeout);
+        if tls {
+            let mut config = ClientTlsConfig::new().with_webpki_roots();
+            if let Some(ca) = ca {
+                config = config.ca_certificate(Certificate::from_pem(ca));
+            }
+            builder = builder.tls_config(config).context("configuring grpc tls")?;
+        }
+        let authorization = authorization
+            .map(|value| MetadataValue::try_from(value.as_bytes()))
+            .transpose()
+            .context("authorization is not valid grpc metadata")?;
+        Ok(Self {
+            channel: builder.connect_lazy(),
+            authorization,
+        })
+    }
+
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(authorization) = &self.authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+        request
+    }
+
+    fn mesh(&self) -> MeshServiceClient<Channel> {
+        MeshServiceClient::new(self.channel.clone())
+    }
+
+    fn node(&self) -> NodeServiceClient<Channel> {
+        NodeServiceClient::new(self.channel.clone())
+    }
+
+    fn smesher(&self) -> SmesherServiceClient<Channel> {
+        SmesherServiceClient::new(self.channel.clone())
+    }
+
+    pub async fn current_epoch(&self) -> Result<i64, RpcError> {
+        let resp = self
+            .mesh()
+            .current_epoch(self.request(CurrentEpochRequest {}))
+            .await?;
+        resp.into_inner()
+            .epochnum
+            .map(|epoch| epoch.number as i64)
+            .ok_or_else(|| missing("epoch"))
+    }
+
+    pub async fn current_layer(&self) -> Result<i64, RpcError> {
+        let resp = self
+            .mesh()
+            .current_layer(self.request(CurrentLayerRequest {}))
+            .await?;
+        resp.into_inner()
+            .layernum
+            .map(|layer| layer.number as i64)
+            .ok_or_else(|| missing("layer"))
+    }
+
+    pub async fn network_info(&self) -> Result<NetworkInfo, RpcError> {
+        let layers = self
+            .mesh()
+            .epoch_num_layers(self.request(EpochNumLayersRequest {}))
+            .await?
+            .into_inner()
+            .numlayers
+            .ok_or_else(|| missing("layers per epoch"))?;
+        let duration = self
+            .mesh()
+            .layer_duration(self.request(LayerDurationRequest {}))
+            .await?
+            .into_inner()
+            .duration
+            .ok_or_else(|| missing("layer duration"))?;
+        Ok(NetworkInfo {
+            layers_per_epoch: layers.number as i64,
+            layer_duration: duration.value as i64,
+        })
+    }
+
+    pub async fn node_status(&self) -> Result<NodeState, RpcError> {
+        let status = self
+            .node()
+            .status(self.request(StatusRequest {}))
+            .await?
+            .into_inner()
+            .status
+            .ok_or_else(|| missing("status"))?;
+        let layer = |layer: Option<proto::LayerNumber>| layer.map_or(0, |l| l.number as i64);
+        Ok(NodeState {
+            connected_peers: status.connected_peers as i64,
+            synced: status.is_synced,
+            synced_layer: layer(status.synced_layer),
+            top_layer: layer(status.top_layer),
+            verified_layer: layer(status.verified_layer),
+        })
+    }
+
+    pub async fn is_smeshing(&self) -> Result<bool, RpcError> {
+        Ok(self
+            .smesher()
+            .is_smeshing(self.request(()))
+            .await?
+            .into_inner()
+            .is_smeshing)
+    }
+
+    pub async fn smesher_ids(&self) -> Result<Vec<String>, RpcError> {
+        let resp = self
+            .smesher()
+            .smesher_i_ds(self.request(()))
+            .await?
+            .into_inner();
+        Ok(resp.public_keys.iter().map(hex::encode).collect())
+    }
+
+    pub async fn coinbase(&self) -> Result<String, RpcError> {
+        self.smesher()
+            .coinbase(self.request(()))
+            .await?
+            .into_inner()
+            .account_id
+            .map(|account| account.address)
+            .ok_or_else(|| missing("coinbase"))
+    }
+
+    pub async fn post_setup_status(&self) -> Result<PostSetup, RpcError> {
+        let status = self
+            .smesher()
+            .post_setup_status(self.request(()))
+            .await?
+            .into_inner()
+            .status
+            .ok_or_else(|| missing("post setup status"))?;
+        Ok(PostSetup {
+            state: PostSetupState::from_proto_name(status.state().as_str_name()),
+            num_labels_written: status.num_labels_written as i64,
+            num_units: status.opts.map_or(0, |opts| opts.num_units as i64),
+        })
+    }
+}
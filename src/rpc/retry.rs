@@ -0,0 +1,81 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+
+/// tries per request, including the first, default of `limits.rpc_retry_attempts`
+pub const RETRY_ATTEMPTS: u32 = 3;
+pub const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(2);
+/// consecutive failed requests that open the circuit, default of `limits.breaker_threshold`
+pub const BREAKER_THRESHOLD: u32 = 5;
+/// how long an open circuit rejects requests before one is let through again,
+/// default of `limits.breaker_cooldown_secs`
+pub const BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// full jitter: anywhere between zero and the exponential delay for `attempt`
+pub fn backoff(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RETRY_MAX_DELAY);
+    delay.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// stops requests to an endpoint after repeated failures, then lets one through per cooldown
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    pub threshold: u32,
+    pub cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::default(),
+        }
+    }
+
+    /// whether a request may go out, half-open after the cooldown until it fails again
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                // the next failure reopens right away, a success closes
+                state.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+
+    pub fn success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    /// returns whether this failure opened the circuit
+    pub fn failure(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures >= self.threshold && state.open_until.is_none() {
+            state.open_until = Some(Instant::now() + self.cooldown);
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    net::SocketAddr,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    extract::ConnectInfo,
+    http::{HeaderValue, Method},
+    Router,
+};
+use hyper::{body::Incoming, service::service_fn, Request};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, UnixListener},
+    time::{sleep, timeout},
+};
+use tokio_rustls::{
+    rustls::{
+        crypto::ring,
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        ServerConfig,
+    },
+    TlsAcceptor,
+};
+use tower::ServiceExt;
+use tower_http::cors::{AllowMethods, AllowOrigin, Any, CorsLayer};
+
+/// how often the certificate files are checked for changes
+pub const CERT_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+/// connections that have not finished the tls handshake by then are dropped
+pub const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// address the api server accepts connections on, `unix:` prefixed paths are unix sockets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some("") => Err("unix socket path is empty".to_string()),
+            Some(path) => Ok(Listen::Unix(PathBuf::from(path))),
+            None => Ok(Listen::Tcp(s.to_string())),
+        }
+    }
+}
+
+/// allowed origin given on the command line, `*` allows any
+pub fn parse_origin(s: &str) -> Result<HeaderValue, String> {
+    if s != "*" && !(s.starts_with("http://") || s.starts_with("https://")) {
+        return Err(format!("expected * or an http(s) origin, got {}", s));
+    }
+    HeaderValue::from_str(s.trim_end_matches('/')).map_err(|e| e.to_string())
+}
+
+/// allowed method given on the command line, case insensitive
+pub fn parse_method(s: &str) -> Result<Method, String> {
+    Method::from_str(&s.to_ascii_uppercase()).map_err(|e| e.to_string())
+}
+
+/// any origin or method when none are configured
+pub fn cors_layer(origins: &[HeaderValue], methods: &[Method]) -> CorsLayer {
+    let origin = if origins.is_empty() || origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(origins.iter().cloned())
+    };
+    let methods = if methods.is_empty() {
+        AllowMethods::from(Any)
+    } else {
+        AllowMethods::list(methods.iter().cloned())
+    };
+    CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(methods)
+        .allow_headers(Any)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// certificate chain and key read from pem files, replaced when the files change
+#[derive(Debug)]
+pub struct ReloadingCert {
+    cert: PathBuf,
+    key: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    modified: RwLock<Option<SystemTime>>,
+}
+
+impl ReloadingCert {
+    pub fn load(cert: PathBuf, key: PathBuf) -> io::Result<Arc<Self>> {
+        let modified = Self::modified(&cert, &key);
+        let current = Self::read(&cert, &key)?;
+        Ok(Arc::new(Self {
+            cert,
+            key,
+            current: RwLock::new(Arc::new(current)),
+            modified: RwLock::new(modified),
+        }))
+    }
+
+    fn read(cert: &Path, key: &Path) -> io::Result<CertifiedKey> {
+        let chain = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        if chain.is_empty() {
+            return Err(invalid_data(format!(
+                "no certificate in {}",
+                cert.display()
+            )));
+        }
+        let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key)?))?
+            .ok_or_else(|| invalid_data(format!("no private key in {}", key.display())))?;
+        let key = ring::sign::any_supported_type(&key).map_err(|e| invalid_data(e.to_string()))?;
+        Ok(CertifiedKey::new(chain, key))
+    }
+
+    /// latest modification of either file, renewals usually replace both
+    fn modified(cert: &Path, key: &Path) -> Option<SystemTime> {
+        [cert, key]
+            .iter()
+            .filter_map(|path| path.metadata().and_then(|meta| meta.modified()).ok())
+            .max()
+    }
+
+    /// keeps serving the previous certificate when the new files cannot be loaded
+    pub fn reload_if_changed(&self) {
+        let modified = Self::modified(&self.cert, &self.key);
+        if modified == *self.modified.read().unwrap() {
+            return;
+        }
+        match Self::read(&self.cert, &self.key) {
+            Ok(current) => {
+                *self.current.write().unwrap() = Arc::new(current);
+                *self.modified.write().unwrap() = modified;
+                log::info!("reloaded tls certificate {}", self.cert.display());
+            }
+            Err(e) => log::error!("failed to reload tls certificate: {}", e),
+        }
+    }
+
+    /// what new handshakes are answered with
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub async fn watch(self: Arc<Self>) {
+        loop {
+            sleep(CERT_RELOAD_INTERVAL).await;
+            self.reload_if_changed();
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+pub fn tls_acceptor(cert: Arc<ReloadingCert>) -> io::Result<TlsAcceptor> {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| invalid_data(e.to_string()))?
+        .with_no_client_auth()
+        .with_cert_resolver(cert);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// serves http/1 and http/2, tcp peers are passed to handlers as `ConnectInfo<SocketAddr>`
+async fn serve_io<S>(router: Router, stream: S, peer: Option<SocketAddr>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |mut req: Request<Incoming>| {
+        if let Some(peer) = peer {
+            req.extensions_mut().insert(ConnectInfo(peer));
+        }
+        router.clone().oneshot(req)
+    });
+    if let Err(e) = Builder::new(TokioExecutor::new())
+        .serve_connection_with_upgrades(TokioIo::new(stream), service)
+        .await
+    {
+        log::debug!("connection error: {}", e);
+    }
+}
+
+async fn serve_connection<S>(
+    router: Router,
+    stream: S,
+    peer: Option<SocketAddr>,
+    tls: Option<TlsAcceptor>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let Some(acceptor) = tls else {
+        return serve_io(router, stream, peer).await;
+    };
+    match timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => serve_io(router, stream, peer).await,
+        Ok(Err(e)) => log::debug!("tls handshake failed: {}", e),
+        Err(_) => log::debug!("tls handshake timed out"),
+    }
+}
+
+/// accepts until the process exits, a failed accept only delays the next one
+pub async fn serve(router: Router, listen: Listen, tls: Option<TlsAcceptor>) -> io::Result<()> {
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    match listen {
+        Listen::Tcp(addr) => {
+            let listener = TcpListener::bind(&addr).await?;
+            log::info!("api server starts listening at {}://{}", scheme, addr);
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        tokio::spawn(serve_connection(
+                            router.clone(),
+                            stream,
+                            Some(peer),
+                            tls.clone(),
+                        ));
+                    }
+                    Err(e) => {
+                        log::warn!("accept failed: {}", e);
+                        sleep(Duration::from_millis(100)).await;
+                    }
+                }
+            }
+        }
+        Listen::Unix(path) => {
+            // a socket left behind by a previous run would make bind fail
+            if std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                std::fs::remove_file(&path)?;
+            }
+            let listener = UnixListener::bind(&path)?;
+            log::info!(
+                "api server starts listening at {} on {}",
+                scheme,
+                path.display()
+            );
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(serve_connection(router.clone(), stream, None, tls.clone()));
+                    }
+                    Err(e) => {
+                        log::warn!("accept failed: {}", e);
+                        sleep(Duration::from_millis(100)).await;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder};
+use tokio::time::sleep;
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_now,
+    metrics::TrackDbError,
+    rpc::{PostSetupState, Smesher},
+    DBHandler, Shared,
+};
+
+pub const SMESHER_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// post setup and smeshing state of an identity as last reported by its node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct SmesherState {
+    pub state: PostSetupState,
+    pub smeshing: bool,
+    pub num_labels_written: i64,
+    /// as configured for the post setup, the `post` table may lag behind
+    pub num_units: i64,
+    /// unix time of the refresh that last saw this state change
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct KeySmesherState {
+    pub id: String,
+    #[sqlx(flatten)]
+    pub state: SmesherState,
+}
+
+impl DBHandler {
+    /// replaces what `node` reported before, so identities it stopped reporting are dropped;
+    /// unchanged identities are left alone, returns how many rows changed
+    pub async fn save_smesher_states(
+        &self,
+        node: &str,
+        smeshers: &[Smesher],
+        updated_at: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.poolstats.begin().await.track("poolstats")?;
+        let mut query = QueryBuilder::new("DELETE FROM smesher_states WHERE node = ");
+        query.push_bind(node).push(" AND id NOT IN (");
+        let mut separated = query.separated(", ");
+        for smesher in smeshers {
+            separated.push_bind(&smesher.id);
+        }
+        query.push(")");
+        let mut changed = query
+            .build()
+            .execute(&mut *tx)
+            .await
+            .track("poolstats")?
+            .rows_affected();
+        for smesher in smeshers {
+            let result = sqlx::query(
+                "INSERT INTO smesher_states (id, node, state, smeshing, num_labels_written, num_units, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (id) DO UPDATE SET node = excluded.node, state = excluded.state, smeshing = excluded.smeshing, num_labels_written = excluded.num_labels_written, num_units = excluded.num_units, updated_at = excluded.updated_at
+                WHERE (node, state, smeshing, num_labels_written, num_units) IS NOT (excluded.node, excluded.state, excluded.smeshing, excluded.num_labels_written, excluded.num_units)",
+            )
+            .bind(&smesher.id)
+            .bind(node)
+            .bind(smesher.post.state)
+            .bind(smesher.smeshing)
+            .bind(smesher.post.num_labels_written)
+            .bind(smesher.post.num_units)
+            .bind(updated_at)
+            .execute(&mut *tx)
+            .await
+            .track("poolstats")?;
+            changed += result.rows_affected();
+        }
+        tx.commit().await.track("poolstats")?;
+        Ok(changed)
+    }
+
+    /// drops identities reported by nodes other than `nodes`, e.g. ones removed from the config
+    pub async fn forget_smesher_nodes(&self, nodes: &[String]) -> Result<u64, sqlx::Error> {
+        let mut query = QueryBuilder::new("DELETE FROM smesher_states WHERE node NOT IN (");
+        let mut separated = query.separated(", ");
+        for node in nodes {
+            separated.push_bind(node);
+        }
+        query.push(")");
+        let result = query
+            .build()
+            .execute(&self.poolstats)
+            .await
+            .track("poolstats")?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_smesher_states(&self) -> Result<HashMap<String, SmesherState>, sqlx::Error> {
+        let rows: Vec<KeySmesherState> = sqlx::query_as(
+            "SELECT id, state, smeshing, num_labels_written, num_units, updated_at FROM smesher_states",
+        )
+        .fetch_all(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(rows.into_iter().map(|row| (row.id, row.state)).collect())
+    }
+
+    /// unix time of the latest change, none before the first refresh
+    pub async fn last_smesher_update(&self) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX (updated_at) FROM smesher_states")
+            .fetch_one(&self.poolstats)
+            .await
+            .track("poolstats")
+    }
+
+    /// setups that are not complete yet, failed and unknown ones are left out
+    pub async fn count_initializing(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COUNT (*) FROM smesher_states WHERE state IN ('not_started', 'prepared', 'in_progress', 'paused')",
+        )
+        .fetch_one(&self.poolstats)
+        .await
+        .track("poolstats")
+    }
+
+    pub async fn initializing_num_units(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COALESCE (SUM (num_units), 0) FROM smesher_states WHERE state IN ('not_started', 'prepared', 'in_progress', 'paused')",
+        )
+        .fetch_one(&self.poolstats)
+        .await
+        .track("poolstats")
+    }
+}
+
+/// stores what the nodes report for each of their identities; what a node that did not answer
+/// reported before is kept
+pub async fn refresh(shared: &Shared) -> anyhow::Result<usize> {
+    let reports = shared.rpc_handler.smeshers().await?;
+    let updated_at = unix_now();
+    let db = &shared.db_handler;
+    let mut changed = 0;
+    for (node, smeshers) in &reports {
+        changed += db.save_smesher_states(node, smeshers, updated_at).await?;
+    }
+    let nodes: Vec<String> = shared
+        .rpc_handler
+        .health()
+        .into_iter()
+        .map(|health| health.endpoint)
+        .collect();
+    changed += db.forget_smesher_nodes(&nodes).await?;
+    // an unchanged refresh keeps every etag valid
+    if changed > 0 {
+        db.bump_generation().await?;
+    }
+    Ok(reports.values().map(Vec::len).sum())
+}
+
+pub async fn run(shared: Arc<Shared>) {
+    loop {
+        match refresh(&shared).await {
+            Ok(count) => log::debug!("refreshed {} smesher states", count),
+            Err(e) => log::warn!("smesher state refresh failed: {:?}", e),
+        }
+        sleep(shared.config.intervals.smesher()).await;
+    }
+}
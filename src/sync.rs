@@ -0,0 +1,345 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::extract::State;
+use log::info;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use tokio::time::sleep;
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_now,
+    error::{ApiError, ApiResponse},
+    events::PoolEvent,
+    metrics::{TrackDbError, METRICS},
+    poolstats::{AtxInfo, Key, Registeration},
+    rpc::EndpointHealth,
+    DBHandler, Shared,
+};
+
+/// keys read from `post` per query, default of `limits.sync_batch`
+pub const SYNC_BATCH: i64 = 50;
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// what the previous passes saw, to tell rollovers apart from restarts and to report once
+#[derive(Debug, Clone, Default)]
+pub struct SyncState {
+    pub epoch: Option<i64>,
+    pub window_open: bool,
+    /// (id, epoch) of keys already reported without an atx, again after a restart
+    pub atx_missing: HashSet<(String, i64)>,
+}
+
+/// a completed sync pass, persisted so other processes can tell how fresh poolstats is
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct SyncPass {
+    pub id: i64,
+    pub epoch: i64,
+    pub layer: i64,
+    pub keys: i64,
+    pub started_at: i64,
+    pub finished_at: i64,
+}
+
+impl SyncPass {
+    /// seconds since the pass finished
+    pub fn age(&self) -> i64 {
+        (unix_now() - self.finished_at).max(0)
+    }
+}
+
+impl DBHandler {
+    /// records a finished pass and returns it with its id
+    pub async fn save_sync_pass(&self, mut pass: SyncPass) -> Result<SyncPass, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO sync_passes (epoch, layer, keys, started_at, finished_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(pass.epoch)
+        .bind(pass.layer)
+        .bind(pass.keys)
+        .bind(pass.started_at)
+        .bind(pass.finished_at)
+        .execute(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        pass.id = result.last_insert_rowid();
+        Ok(pass)
+    }
+
+    pub async fn last_sync_pass(&self) -> Result<Option<SyncPass>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, epoch, layer, keys, started_at, finished_at FROM sync_passes ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_optional(&self.poolstats)
+        .await
+        .track("poolstats")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct SyncStatus {
+    pub last_pass: Option<SyncPass>,
+    /// seconds since `last_pass` finished
+    pub age: Option<i64>,
+    pub endpoints: Vec<EndpointHealth>,
+    /// an endpoint disagrees with the others on epoch or layer
+    pub diverged: bool,
+}
+
+/// freshness of the synced data and health of the node endpoints it was read from
+#[utoipa::path(
+    get,
+    path = "/sync_status",
+    tag = "pool",
+    responses(
+        (status = 200, body = SyncStatusResponse),
+        (status = 500, description = "cache db failed", body = ErrorBody),
+    )
+)]
+pub async fn sync_status_handler(
+    State(shared): State<Arc<Shared>>,
+) -> Result<ApiResponse<SyncStatus>, ApiError> {
+    let last_pass = shared.db_handler.last_sync_pass().await?;
+    let endpoints = shared.rpc_handler.health();
+    let status = SyncStatus {
+        age: last_pass.as_ref().map(SyncPass::age),
+        last_pass,
+        diverged: endpoints.iter().any(|endpoint| endpoint.diverged),
+        endpoints,
+    };
+    Ok(ApiResponse::new(status, false))
+}
+
+pub async fn run(shared: Arc<Shared>) {
+    let mut state = SyncState::default();
+    loop {
+        if let Err(e) = run_pass(&shared, &mut state).await {
+            log::error!("sync pass failed: {:?}", e);
+        }
+        sleep(shared.config.intervals.sync()).await;
+    }
+}
+
+/// one pass, recorded in the cache once it completed
+pub async fn run_pass(shared: &Shared, state: &mut SyncState) -> anyhow::Result<SyncPass> {
+    let started = Instant::now();
+    let pass = sync_pass(shared, state).await;
+    // a failed pass may still have stored rows
+    let bumped = shared.db_handler.bump_generation().await;
+    let pass = pass?;
+    bumped?;
+    METRICS.sync_pass_completed(started.elapsed());
+    Ok(shared.db_handler.save_sync_pass(pass).await?)
+}
+
+/// copies poet registrations and atxs of every initialized key into poolstats
+pub async fn sync_pass(shared: &Shared, state: &mut SyncState) -> anyhow::Result<SyncPass> {
+    let started = Instant::now();
+    let started_at = unix_now();
+    let clock = shared
+        .clock
+        .refresh(&shared.rpc_handler, &shared.config.network)
+        .await?;
+    let (epoch_info, current_layer) = (clock.epoch, clock.layer);
+    let round_id = (epoch_info - 1).to_string();
+    let mut next_round = None;
+    let mut next_epoch = None;
+    if clock.registration_open(&shared.config.network) {
+        next_round = Some(epoch_info.to_string());
+        next_epoch = Some(epoch_info);
+    }
+    if state.epoch.is_some_and(|epoch| epoch < epoch_info) {
+        shared.publish(PoolEvent::EpochRollover { epoch: epoch_info });
+        state.window_open = false;
+    }
+    if state.epoch.is_some() && !state.window_open && next_round.is_some() {
+        shared.publish(PoolEvent::RegistrationWindowOpened {
+            round_id: epoch_info.to_string(),
+            layer: current_layer,
+        });
+    }
+    state.epoch = Some(epoch_info);
+    state.window_open = next_round.is_some();
+    state
+        .atx_missing
+        .retain(|(_, epoch)| *epoch >= epoch_info - 1);
+
+    let db = &shared.db_handler;
+    let count = db.count_initialzed().await?;
+    let num_units = db.inited_num_units().await?;
+    db.save_init_snapshot(epoch_info - 1, count, num_units)
+        .await?;
+    let mut cursor = None;
+    loop {
+        let keys = db
+            .get_init_keys(shared.config.limits.sync_batch, cursor.clone())
+            .await?;
+        if keys.is_empty() {
+            break;
+        }
+        cursor = keys.last().map(|key| key.id.clone());
+        info!("init posted keys {:?}", keys);
+        for Key { id, num_units } in keys {
+            let mut registered = false;
+            if let Ok(registerations) = db
+                .get_chain_registerations_by_id(id.clone(), round_id.clone())
+                .await
+            {
+                registered = !registerations.is_empty();
+                if registered {
+                    store_poet(shared, &id, num_units, registerations[0].clone()).await;
+                }
+            }
+            match db.get_chain_atxs_by_id(id.clone(), epoch_info - 1).await {
+                Ok(atx) => {
+                    store_atx(shared, &id, num_units, atx).await;
+                }
+                Err(sqlx::Error::RowNotFound) if registered => {
+                    if state.atx_missing.insert((id.clone(), epoch_info - 1)) {
+                        shared.publish(PoolEvent::AtxMissing {
+                            id: id.clone(),
+                            epoch: epoch_info - 1,
+                        });
+                    }
+                }
+                Err(e) => {
+                    log::error!("{:?}", e)
+                }
+            }
+
+            if let Some(round_id) = next_round.clone() {
+                if let Ok(registerations) = db
+                    .get_chain_registerations_by_id(id.clone(), round_id.clone())
+                    .await
+                {
+                    if !registerations.is_empty() {
+                        store_poet(shared, &id, num_units, registerations[0].clone()).await;
+                    }
+                }
+            }
+
+            if let Some(epoch) = next_epoch {
+                match db.get_chain_atxs_by_id(id.clone(), epoch).await {
+                    Ok(atx) => {
+                        store_atx(shared, &id, num_units, atx).await;
+                    }
+                    Err(e) => {
+                        log::error!("{:?}", e)
+                    }
+                }
+            }
+        }
+    }
+    shared.publish(PoolEvent::SyncCompleted {
+        epoch: epoch_info,
+        layer: current_layer,
+        keys: count,
+        duration_ms: started.elapsed().as_millis() as u64,
+    });
+    Ok(SyncPass {
+        id: 0,
+        epoch: epoch_info,
+        layer: current_layer,
+        keys: count,
+        started_at,
+        finished_at: unix_now(),
+    })
+}
+
+/// what a backfill added to the cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Backfilled {
+    pub keys: i64,
+    pub registrations: i64,
+    pub atxs: i64,
+}
+
+/// copies poet registrations and atxs of every initialized key for a past `epoch`, numbered
+/// as by `/overview`; the initialized totals of that epoch are not known anymore and stay unset
+pub async fn backfill_epoch(shared: &Shared, epoch: i64) -> anyhow::Result<Backfilled> {
+    let db = &shared.db_handler;
+    let round_id = epoch.to_string();
+    let mut backfilled = Backfilled::default();
+    let mut cursor = None;
+    loop {
+        let keys = db
+            .get_init_keys(shared.config.limits.sync_batch, cursor.clone())
+            .await?;
+        if keys.is_empty() {
+            break;
+        }
+        cursor = keys.last().map(|key| key.id.clone());
+        for Key { id, num_units } in keys {
+            backfilled.keys += 1;
+            let registerations = db
+                .get_chain_registerations_by_id(id.clone(), round_id.clone())
+                .await?;
+            if let Some(registeration) = registerations.into_iter().next() {
+                if store_poet(shared, &id, num_units, registeration).await {
+                    backfilled.registrations += 1;
+                }
+            }
+            match db.get_chain_atxs_by_id(id.clone(), epoch).await {
+                Ok(atx) => {
+                    if store_atx(shared, &id, num_units, atx).await {
+                        backfilled.atxs += 1;
+                    }
+                }
+                Err(sqlx::Error::RowNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    if backfilled.registrations + backfilled.atxs > 0 {
+        db.bump_generation().await?;
+    }
+    Ok(backfilled)
+}
+
+/// returns whether the registration was new
+async fn store_poet(
+    shared: &Shared,
+    id: &str,
+    num_units: i64,
+    registeration: Registeration,
+) -> bool {
+    let round_id = registeration.round_id.clone();
+    let saved = matches!(
+        shared
+            .db_handler
+            .save_poet(id.to_string(), num_units, registeration)
+            .await,
+        Ok(true)
+    );
+    if saved {
+        shared.publish(PoolEvent::PoetRegistrationSaved {
+            id: id.to_string(),
+            num_units,
+            round_id,
+        });
+    }
+    saved
+}
+
+/// returns whether the atx was new
+async fn store_atx(shared: &Shared, id: &str, num_units: i64, atx: AtxInfo) -> bool {
+    let saved = matches!(
+        shared
+            .db_handler
+            .save_atx(id.to_string(), num_units, atx.clone())
+            .await,
+        Ok(true)
+    );
+    if saved {
+        shared.publish(PoolEvent::AtxSaved {
+            id: id.to_string(),
+            num_units,
+            atx,
+        });
+    }
+    saved
+}
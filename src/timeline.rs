@@ -0,0 +1,220 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::extract::{rejection::QueryRejection, Query, State};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    error::{ApiError, ApiResponse},
+    metrics::TrackDbError,
+    poolstats::Item,
+    DBHandler, Shared,
+};
+
+/// upper bound of epochs returned by one timeline request, default of `limits.max_timeline_epochs`
+pub const MAX_TIMELINE_EPOCHS: i64 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimelineQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+struct EpochRow {
+    epoch: i64,
+    count: i64,
+    num_units: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct TimelineEntry {
+    pub epoch: i64,
+    pub registerd: Item,
+    pub actived: Item,
+    /// initialized totals last seen while `epoch` was current, null before snapshots existed
+    pub init_posted: Option<Item>,
+    /// registered to the epoch's poet round but no atx published
+    pub missed: i64,
+}
+
+impl TimelineEntry {
+    fn new(epoch: i64) -> Self {
+        Self {
+            epoch,
+            registerd: Item::new(0, 0),
+            actived: Item::new(0, 0),
+            init_posted: None,
+            missed: 0,
+        }
+    }
+}
+
+impl DBHandler {
+    pub async fn save_init_snapshot(
+        &self,
+        epoch: i64,
+        count: i64,
+        num_units: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO init_snapshots (epoch, count, num_units) VALUES ($1, $2, $3) ON CONFLICT (epoch) DO UPDATE SET count = excluded.count, num_units = excluded.num_units",
+        )
+        .bind(epoch)
+        .bind(count)
+        .bind(num_units)
+        .execute(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(())
+    }
+
+    pub async fn get_init_snapshot(&self, epoch: i64) -> Result<Option<Item>, sqlx::Error> {
+        let result: Option<EpochRow> =
+            sqlx::query_as("SELECT epoch, count, num_units FROM init_snapshots WHERE epoch = $1")
+                .bind(epoch)
+                .fetch_optional(&self.poolstats)
+                .await
+                .track("poolstats")?;
+        Ok(result.map(|row| Item::new(row.count, row.num_units)))
+    }
+
+    pub async fn stored_epoch_range(&self) -> Result<Option<(i64, i64)>, sqlx::Error> {
+        let result: (Option<i64>, Option<i64>) = sqlx::query_as(
+            "SELECT MIN (epoch), MAX (epoch) FROM (SELECT epoch FROM atxs UNION SELECT CAST (round_id AS INT) FROM poet_registration)",
+        )
+        .fetch_one(&self.poolstats)
+        .await
+        .track("poolstats")?;
+        Ok(result.0.zip(result.1))
+    }
+
+    async fn timeline_rows(
+        &self,
+        sql: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<EpochRow>, sqlx::Error> {
+        sqlx::query_as(sql)
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.poolstats)
+            .await
+            .track("poolstats")
+    }
+
+    pub async fn get_timeline(
+        &self,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<TimelineEntry>, sqlx::Error> {
+        let mut entries: BTreeMap<i64, TimelineEntry> = (from..=to)
+            .map(|epoch| (epoch, TimelineEntry::new(epoch)))
+            .collect();
+        let registered = self
+            .timeline_rows(
+                "SELECT CAST (round_id AS INT) AS epoch, COUNT (*) AS count, SUM (num_unit) AS num_units FROM poet_registration WHERE CAST (round_id AS INT) BETWEEN $1 AND $2 GROUP BY round_id",
+                from,
+                to,
+            )
+            .await?;
+        for row in registered {
+            if let Some(entry) = entries.get_mut(&row.epoch) {
+                entry.registerd = Item::new(row.count, row.num_units);
+            }
+        }
+        let actived = self
+            .timeline_rows(
+                "SELECT epoch, COUNT (*) AS count, SUM (effective_num_units) AS num_units FROM atxs WHERE epoch BETWEEN $1 AND $2 GROUP BY epoch",
+                from,
+                to,
+            )
+            .await?;
+        for row in actived {
+            if let Some(entry) = entries.get_mut(&row.epoch) {
+                entry.actived = Item::new(row.count, row.num_units);
+            }
+        }
+        let missed = self
+            .timeline_rows(
+                "SELECT CAST (p.round_id AS INT) AS epoch, COUNT (*) AS count, SUM (p.num_unit) AS num_units FROM poet_registration p LEFT JOIN atxs a ON a.id = p.id AND a.epoch = CAST (p.round_id AS INT) WHERE a.id IS NULL AND CAST (p.round_id AS INT) BETWEEN $1 AND $2 GROUP BY p.round_id",
+                from,
+                to,
+            )
+            .await?;
+        for row in missed {
+            if let Some(entry) = entries.get_mut(&row.epoch) {
+                entry.missed = row.count;
+            }
+        }
+        let snapshots = self
+            .timeline_rows(
+                "SELECT epoch, count, num_units FROM init_snapshots WHERE epoch BETWEEN $1 AND $2",
+                from,
+                to,
+            )
+            .await?;
+        for row in snapshots {
+            if let Some(entry) = entries.get_mut(&row.epoch) {
+                entry.init_posted = Some(Item::new(row.count, row.num_units));
+            }
+        }
+        Ok(entries.into_values().collect())
+    }
+}
+
+/// the epochs a query asks for given the `first` and `last` stored, the latest `max` by default
+pub fn timeline_range(
+    query: &TimelineQuery,
+    first: i64,
+    last: i64,
+    max: i64,
+) -> Result<(i64, i64), ApiError> {
+    let to = query.to.unwrap_or(last);
+    let from = query
+        .from
+        .unwrap_or_else(|| first.max(to.saturating_sub(max - 1)));
+    if from > to {
+        return Err(ApiError::bad_request("invalid_query", "from is after to"));
+    }
+    // the span of extreme bounds does not fit an i64
+    if to.checked_sub(from).is_none_or(|span| span >= max) {
+        return Err(ApiError::bad_request(
+            "invalid_query",
+            format!("at most {} epochs per request", max),
+        ));
+    }
+    Ok((from, to))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeline",
+    tag = "pool",
+    params(TimelineQuery),
+    responses(
+        (status = 200, body = TimelineResponse),
+        (status = 304, description = "unchanged since the etag in If-None-Match"),
+        (status = 400, description = "invalid or too wide range", body = ErrorBody),
+        (status = 503, description = "database unavailable", body = ErrorBody),
+    )
+)]
+pub async fn timeline_handler(
+    State(shared): State<Arc<Shared>>,
+    query: Result<Query<TimelineQuery>, QueryRejection>,
+) -> Result<ApiResponse<Vec<TimelineEntry>>, ApiError> {
+    let Query(query) = query?;
+    let Some((first, last)) = shared.db_handler.stored_epoch_range().await? else {
+        return Ok(ApiResponse::new(vec![], false));
+    };
+    let (from, to) = timeline_range(
+        &query,
+        first,
+        last,
+        shared.config.limits.max_timeline_epochs,
+    )?;
+    let timeline = shared.db_handler.get_timeline(from, to).await?;
+    Ok(ApiResponse::new(timeline, false))
+}
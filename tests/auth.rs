@@ -0,0 +1,173 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::Extension,
+    http::{header, Request, StatusCode},
+    middleware,
+    routing::get,
+    Router,
+};
+use poolstats::{
+    auth::{hash_key, require_scope, ApiKey, Scope, API_KEY_HEADER},
+    config::Config,
+    Shared,
+};
+use tower::ServiceExt;
+
+async fn shared(name: &str) -> Arc<Shared> {
+    common::shared(common::databases(name, &[]).await, Config::default())
+}
+
+fn router(shared: &Arc<Shared>, scope: Scope) -> Router {
+    Router::new()
+        .route(
+            "/",
+            get(|key: Option<Extension<ApiKey>>| async move {
+                key.map(|Extension(key)| key.name).unwrap_or_default()
+            }),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            (shared.clone(), scope),
+            require_scope,
+        ))
+}
+
+async fn status(router: Router, header: Option<(&str, String)>) -> StatusCode {
+    let mut request = Request::get("/");
+    if let Some((name, value)) = header {
+        request = request.header(name, value);
+    }
+    let request = request.body(Body::empty()).unwrap();
+    router.oneshot(request).await.unwrap().status()
+}
+
+#[test]
+fn scopes_are_ordered() {
+    assert!(Scope::Public < Scope::Private);
+    assert!(Scope::Private < Scope::Admin);
+    for scope in [Scope::Public, Scope::Private, Scope::Admin] {
+        let key = ApiKey {
+            id: 1,
+            name: "pool".to_string(),
+            scope: scope.as_str().to_string(),
+            created_at: 0,
+            revoked_at: None,
+        };
+        assert_eq!(key.scope(), Some(scope));
+    }
+    let key = ApiKey {
+        id: 1,
+        name: "pool".to_string(),
+        scope: "root".to_string(),
+        created_at: 0,
+        revoked_at: None,
+    };
+    assert_eq!(key.scope(), None);
+}
+
+#[test]
+fn hashes_keys_with_sha256() {
+    assert_eq!(
+        hash_key("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_ne!(hash_key("ps_a"), hash_key("ps_b"));
+}
+
+#[tokio::test]
+async fn creates_finds_and_revokes_keys() {
+    let shared = shared("keys").await;
+    let db = &shared.db_handler;
+    let first = db
+        .create_api_key("first".to_string(), Scope::Private)
+        .await
+        .unwrap();
+    let second = db
+        .create_api_key("second".to_string(), Scope::Admin)
+        .await
+        .unwrap();
+    assert!(first.key.starts_with("ps_"));
+    assert_ne!(first.key, second.key);
+    assert!(second.id > first.id);
+
+    let found = db.find_api_key(&first.key).await.unwrap().unwrap();
+    assert_eq!(found.id, first.id);
+    assert_eq!(found.name, "first");
+    assert_eq!(found.scope(), Some(Scope::Private));
+    // only the hash is stored, so the hash itself is not a key
+    assert_eq!(db.find_api_key(&hash_key(&first.key)).await.unwrap(), None);
+    assert_eq!(db.find_api_key("ps_unknown").await.unwrap(), None);
+
+    assert!(db.revoke_api_key(first.id).await.unwrap());
+    assert!(!db.revoke_api_key(first.id).await.unwrap());
+    assert!(!db.revoke_api_key(second.id + 1).await.unwrap());
+    assert_eq!(db.find_api_key(&first.key).await.unwrap(), None);
+
+    let keys = db.list_api_keys().await.unwrap();
+    assert_eq!(
+        keys.iter().map(|key| key.id).collect::<Vec<_>>(),
+        [first.id, second.id]
+    );
+    assert!(keys[0].revoked_at.is_some());
+    assert_eq!(keys[1].revoked_at, None);
+}
+
+#[tokio::test]
+async fn requires_the_scope_or_above() {
+    let shared = shared("scopes").await;
+    let db = &shared.db_handler;
+    let mut keys = vec![];
+    for scope in [Scope::Public, Scope::Private, Scope::Admin] {
+        keys.push(
+            db.create_api_key(scope.as_str().to_string(), scope)
+                .await
+                .unwrap()
+                .key,
+        );
+    }
+
+    for (required, scope) in [Scope::Public, Scope::Private, Scope::Admin]
+        .into_iter()
+        .enumerate()
+    {
+        for (granted, key) in keys.iter().enumerate() {
+            let want = if granted >= required {
+                StatusCode::OK
+            } else {
+                StatusCode::FORBIDDEN
+            };
+            let header = Some((API_KEY_HEADER, key.clone()));
+            assert_eq!(status(router(&shared, scope), header).await, want);
+            let bearer = Some((header::AUTHORIZATION.as_str(), format!("Bearer {}", key)));
+            assert_eq!(status(router(&shared, scope), bearer).await, want);
+        }
+        let unknown = Some((API_KEY_HEADER, "ps_unknown".to_string()));
+        assert_eq!(
+            status(router(&shared, scope), unknown).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let anonymous = if scope == Scope::Public {
+            StatusCode::OK
+        } else {
+            StatusCode::UNAUTHORIZED
+        };
+        assert_eq!(status(router(&shared, scope), None).await, anonymous);
+    }
+
+    // the key reaches the handler
+    let request = Request::get("/")
+        .header(API_KEY_HEADER, &keys[2])
+        .body(Body::empty())
+        .unwrap();
+    let response = router(&shared, Scope::Private)
+        .oneshot(request)
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"admin");
+}
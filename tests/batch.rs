@@ -0,0 +1,115 @@
+mod common;
+
+use axum::{extract::State, http::StatusCode, Json};
+use poolstats::{
+    clock::{unix_now, ClockState},
+    config::Config,
+    poolstats::{get_nodes_info_batch, validate_id, AtxInfo, BatchRequest, InvalidId, NodeStatus},
+};
+
+#[test]
+fn validates_ids() {
+    let id = "ab".repeat(32);
+    assert_eq!(validate_id(&id), Ok(id.clone()));
+    assert_eq!(validate_id(&id.to_uppercase()), Ok(id.clone()));
+
+    for short in ["", "ab", &"ab".repeat(31)] {
+        assert_eq!(
+            validate_id(short),
+            Err(format!("expected 32 bytes, got {}", short.len() / 2))
+        );
+    }
+    assert_eq!(
+        validate_id(&"ab".repeat(33)),
+        Err("expected 32 bytes, got 33".to_string())
+    );
+    for malformed in [
+        format!("0x{}", "ab".repeat(31)),
+        format!("{}zz", "ab".repeat(31)),
+        format!("{}a", "ab".repeat(31)),
+        format!(" {}", "ab".repeat(32)),
+    ] {
+        let error = validate_id(&malformed).unwrap_err();
+        assert!(!error.starts_with("expected"), "{}: {}", malformed, error);
+    }
+}
+
+#[tokio::test]
+async fn reports_invalid_and_unknown_ids() {
+    let keys = [([1u8; 32].to_vec(), 4), ([2u8; 32].to_vec(), 8)];
+    let db = common::databases("batch", &keys).await;
+    let atx = AtxInfo {
+        epoch: 9,
+        atx_id: hex::encode([9; 32]),
+        effective_num_units: 4,
+        coinbase: hex::encode([1; 24]),
+    };
+    db.save_atx(hex::encode([1; 32]), 4, atx.clone())
+        .await
+        .unwrap();
+    let shared = common::shared(db, Config::default());
+    shared.clock.set(ClockState {
+        epoch: 10,
+        layer: 10 * 4032,
+        refreshed_at: unix_now(),
+    });
+
+    let ids = vec![
+        hex::encode([1; 32]).to_uppercase(),
+        hex::encode([3; 32]),
+        "0x01".to_string(),
+        hex::encode([2; 32]),
+        hex::encode([1; 32]),
+        hex::encode([3; 32]).to_uppercase(),
+        "ab".to_string(),
+    ];
+    let response = get_nodes_info_batch(State(shared.clone()), Ok(Json(BatchRequest { ids })))
+        .await
+        .unwrap();
+    assert!(!response.partial);
+    let batch = response.data;
+
+    let found: Vec<_> = batch
+        .found
+        .iter()
+        .map(|node| (node.id.clone(), node.num_units, node.status))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (hex::encode([1; 32]), 4, NodeStatus::Active),
+            (hex::encode([2; 32]), 8, NodeStatus::Missing),
+        ]
+    );
+    assert_eq!(batch.found[0].atx, atx);
+    // unknown ids are reported once, normalized like found ones
+    assert_eq!(batch.not_found, [hex::encode([3; 32])]);
+    assert_eq!(
+        batch.invalid,
+        [
+            InvalidId {
+                id: "0x01".to_string(),
+                error: validate_id("0x01").unwrap_err(),
+            },
+            InvalidId {
+                id: "ab".to_string(),
+                error: "expected 32 bytes, got 1".to_string(),
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn rejects_too_many_ids() {
+    let db = common::databases("batch-limit", &[]).await;
+    let mut config = Config::default();
+    config.limits.max_batch_ids = 2;
+    let shared = common::shared(db, config);
+    let ids = vec![hex::encode([1; 32]); 3];
+    let error = get_nodes_info_batch(State(shared), Ok(Json(BatchRequest { ids })))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error.status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "too_many_ids");
+}
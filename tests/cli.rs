@@ -0,0 +1,332 @@
+mod common;
+
+use std::{
+    net::TcpListener,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use poolstats::{auth::Scope, sync::SyncPass};
+
+const BIN: &str = env!("CARGO_BIN_EXE_poolstats");
+
+/// the binary with only the cache db of `databases(name, ..)`
+fn bare(name: &str) -> Command {
+    let mut command = Command::new(BIN);
+    command
+        .env_clear()
+        .arg("--datadir")
+        .arg(common::data_dir(name));
+    command
+}
+
+/// the binary with the dbs of `databases(name, ..)` and a node nothing listens on
+fn poolstats(name: &str) -> Command {
+    let dir = common::data_dir(name);
+    let mut command = Command::new(BIN);
+    command
+        .env_clear()
+        .env("POOLSTATS_SOURCES_NODE_API", "v1")
+        .arg("--db")
+        .arg(dir.join("state.sql"))
+        .arg("--local")
+        .arg(dir.join("local.sql"))
+        .arg("--datadir")
+        .arg(&dir)
+        .args(["--node", "127.0.0.1:1"]);
+    command
+}
+
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn serve(name: &str, args: &[&str]) -> (Server, String) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let listen = format!("127.0.0.1:{}", port);
+    let child = poolstats(name)
+        .args(["serve", "--listen", &listen])
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let server = Server(child);
+    let url = format!("http://{}", listen);
+    for _ in 0..100 {
+        if reqwest::get(format!("{}/healthz", url)).await.is_ok() {
+            return (server, url);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("serve did not start");
+}
+
+#[tokio::test]
+async fn serves_without_syncing() {
+    let db = common::databases("cli-serve", &[]).await;
+    let key = db
+        .create_api_key("pool".to_string(), Scope::Private)
+        .await
+        .unwrap()
+        .key;
+    let pass = db
+        .save_sync_pass(SyncPass {
+            id: 0,
+            epoch: 10,
+            layer: 40400,
+            keys: 12,
+            started_at: 1_700_000_000,
+            finished_at: 1_700_000_042,
+        })
+        .await
+        .unwrap();
+    let (_server, url) = serve("cli-serve", &[]).await;
+    let client = reqwest::Client::new();
+
+    // nothing in the process publishes events
+    let resp = client
+        .get(format!("{}/events", url))
+        .header("x-api-key", &key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 501);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "events_unavailable");
+
+    // the pass of the separate sync process is what /metrics and /sync_status report
+    let metrics = client
+        .get(format!("{}/metrics", url))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        metrics.contains("sync_last_success_timestamp_seconds 1700000042"),
+        "{}",
+        metrics
+    );
+    assert!(
+        metrics.contains("sync_pass_duration_seconds 42"),
+        "{}",
+        metrics
+    );
+    let status: serde_json::Value = client
+        .get(format!("{}/sync_status", url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["data"]["last_pass"]["id"], pass.id);
+}
+
+#[tokio::test]
+async fn streams_events_when_syncing() {
+    let db = common::databases("cli-sync", &[]).await;
+    let key = db
+        .create_api_key("pool".to_string(), Scope::Private)
+        .await
+        .unwrap()
+        .key;
+    let (_server, url) = serve("cli-sync", &["--sync"]).await;
+    let resp = reqwest::Client::new()
+        .get(format!("{}/events", url))
+        .header("x-api-key", &key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "text/event-stream");
+}
+
+#[tokio::test]
+async fn one_shot_commands_exit() {
+    common::databases("cli-once", &[]).await;
+    let output = poolstats("cli-once")
+        .args(["backfill", "--from", "5", "--to", "4"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--from 5 is after --to 4"), "{}", stderr);
+
+    let output = poolstats("cli-once")
+        .args(["key", "list"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // the node is down, so a sync pass fails rather than recording anything
+    let output = poolstats("cli-once")
+        .args(["sync", "--once"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn requires_only_the_sources_a_command_reads() {
+    common::databases("cli-sources", &[]).await;
+    let output = bare("cli-sources").args(["key", "list"]).output().unwrap();
+    assert!(output.status.success());
+
+    let output = bare("cli-sources")
+        .args(["export", "--epoch", "1"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid sources.local"), "{}", stderr);
+
+    // setup errors take the same path as every other error
+    let output = poolstats("cli-sources")
+        .args(["--node-ca", "missing.pem", "key", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("cannot read node ca missing.pem"),
+        "{}",
+        stderr
+    );
+}
+
+/// what `serve` registers under `/v1` and bare, besides the probes, dashboard and docs
+const API_ROUTES: &[(&str, &str)] = &[
+    ("get", "/overview"),
+    ("get", "/timeline"),
+    ("get", "/clock"),
+    ("get", "/sync_status"),
+    ("get", "/metrics"),
+    ("post", "/nodes_info"),
+    ("post", "/nodes_info/batch"),
+    ("get", "/events"),
+    ("get", "/export/nodes"),
+    ("get", "/admin/keys"),
+    ("post", "/admin/keys"),
+    ("delete", "/admin/keys/{id}"),
+];
+
+#[tokio::test]
+async fn documents_every_api_route() {
+    common::databases("cli-openapi", &[]).await;
+    let (_server, url) = serve("cli-openapi", &[]).await;
+    let client = reqwest::Client::new();
+    let doc: serde_json::Value = client
+        .get(format!("{}/openapi.json", url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(doc["servers"][0]["url"], "/v1");
+    let mut documented: Vec<(String, String)> = doc["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, operations)| {
+            operations
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|method| (method.clone(), path.clone()))
+        })
+        .collect();
+    documented.sort();
+    let mut registered: Vec<(String, String)> = API_ROUTES
+        .iter()
+        .map(|(method, path)| (method.to_string(), path.to_string()))
+        .collect();
+    registered.sort();
+    assert_eq!(documented, registered);
+
+    // unknown routes are 404 whatever the key, so anything else means the route is served
+    for prefix in ["/v1", ""] {
+        for (method, path) in API_ROUTES {
+            let path = format!("{}{}", prefix, path.replace("{id}", "1"));
+            let method = method.to_uppercase().parse().unwrap();
+            let status = client
+                .request(method, format!("{}{}", url, path))
+                .send()
+                .await
+                .unwrap()
+                .status();
+            assert!(
+                status != 404 && status != 405,
+                "{} answered {}",
+                path,
+                status
+            );
+        }
+    }
+    let status = client
+        .get(format!("{}/v1/unknown", url))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn serves_the_dashboard_without_a_key() {
+    let db = common::databases("cli-dashboard", &[]).await;
+    // keys exist, the dashboard still loads and asks for one itself
+    db.create_api_key("pool".to_string(), Scope::Private)
+        .await
+        .unwrap();
+    let (_server, url) = serve("cli-dashboard", &[]).await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let resp = client
+        .get(format!("{}/dashboard", url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 308);
+    assert_eq!(resp.headers()["location"], "/dashboard/");
+
+    for (path, content_type, marker) in [
+        ("/dashboard/", "text/html; charset=utf-8", "<html"),
+        ("/dashboard/index.html", "text/html; charset=utf-8", "<html"),
+        (
+            "/dashboard/app.js",
+            "text/javascript; charset=utf-8",
+            "fetch",
+        ),
+        ("/dashboard/style.css", "text/css; charset=utf-8", "{"),
+    ] {
+        let resp = client.get(format!("{}{}", url, path)).send().await.unwrap();
+        assert_eq!(resp.status(), 200, "{}", path);
+        assert_eq!(resp.headers()["content-type"], content_type, "{}", path);
+        assert!(resp.text().await.unwrap().contains(marker), "{}", path);
+    }
+
+    let resp = client
+        .get(format!("{}/dashboard/missing.js", url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "not_found");
+}
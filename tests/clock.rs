@@ -0,0 +1,66 @@
+use axum::{routing::post, Json, Router};
+use poolstats::{
+    clock::{ChainClock, ClockState},
+    config::Network,
+    rpc::{ApiVersion, RpcHandler},
+};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+fn network() -> Network {
+    Network {
+        layers_per_epoch: 10,
+        poet_registration_offset: 6,
+        layer_duration_secs: 60,
+    }
+}
+
+fn at(layer: i64) -> ClockState {
+    ClockState::at_layer(layer, &network())
+}
+
+#[test]
+fn places_layers_in_their_epoch() {
+    assert_eq!((at(0).epoch, at(9).epoch, at(10).epoch), (0, 0, 1));
+    assert_eq!(at(125).epoch, 12);
+}
+
+#[test]
+fn opens_registration_at_the_offset() {
+    let network = network();
+    assert!(!at(125).registration_open(&network));
+    assert_eq!(at(125).next_registration_layer(&network), 126);
+    assert!(at(126).registration_open(&network));
+    assert!(at(129).registration_open(&network));
+    assert_eq!(at(126).next_registration_layer(&network), 136);
+    // a new epoch closes it again
+    assert!(!at(130).registration_open(&network));
+
+    let clock = at(125);
+    assert_eq!(clock.next_epoch_layer(&network), 130);
+    assert_eq!(clock.estimate(130, &network), clock.refreshed_at + 300);
+}
+
+#[tokio::test]
+async fn refreshes_from_one_layer_answer() {
+    // an epoch that does not match the layer, as another endpoint could answer
+    let node = Router::new()
+        .route(
+            "/v1/mesh/currentepoch",
+            post(|| async { Json(json!({"epochnum": {"number": 99}})) }),
+        )
+        .route(
+            "/v1/mesh/currentlayer",
+            post(|| async { Json(json!({"layernum": {"number": 125}})) }),
+        );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, node).await.unwrap() });
+    let rpc = RpcHandler::new(vec![addr.to_string().parse().unwrap()], ApiVersion::V1).unwrap();
+
+    let clock = ChainClock::default();
+    assert!(clock.current().is_err());
+    let state = clock.refresh(&rpc, &network()).await.unwrap();
+    assert_eq!((state.epoch, state.layer), (12, 125));
+    assert_eq!(clock.get(), Some(state));
+}
@@ -0,0 +1,65 @@
+//! temporary databases shaped like a node's and a migrated cache
+#![allow(dead_code)]
+
+use std::{path::PathBuf, sync::Arc};
+
+use poolstats::{config::Config, rpc::ApiVersion, rpc::RpcHandler, DBHandler, Shared};
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+
+/// where `databases(name, ..)` keeps its files: `local.sql`, `poolstats.sql` and `state.sql`
+pub fn data_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("poolstats-{}-{}", std::process::id(), name))
+}
+
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = data_dir(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub async fn create(path: &PathBuf) -> SqlitePool {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    SqlitePool::connect_with(options).await.unwrap()
+}
+
+/// a node's local db holding `keys` as (id, num_units) in `post`, and an empty migrated cache
+pub async fn databases(name: &str, keys: &[(Vec<u8>, i64)]) -> DBHandler {
+    let dir = temp_dir(name);
+    let local_path = dir.join("local.sql");
+    let local = create(&local_path).await;
+    sqlx::query("CREATE TABLE post (id BLOB PRIMARY KEY, num_units INT NOT NULL)")
+        .execute(&local)
+        .await
+        .unwrap();
+    sqlx::query("CREATE TABLE poet_registration (id BLOB NOT NULL, hash BLOB NOT NULL, address VARCHAR NOT NULL, round_id VARCHAR NOT NULL, round_end INT NOT NULL)")
+        .execute(&local)
+        .await
+        .unwrap();
+    let mut tx = local.begin().await.unwrap();
+    for (id, num_units) in keys {
+        sqlx::query("INSERT INTO post (id, num_units) VALUES ($1, $2)")
+            .bind(id)
+            .bind(num_units)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    local.close().await;
+
+    let cache_path = dir.join("poolstats.sql");
+    let cache = create(&cache_path).await;
+    sqlx::migrate!().run(&cache).await.unwrap();
+    let local = DBHandler::connect_local(local_path.to_str().unwrap(), Some(&cache_path)).unwrap();
+    let chain = create(&dir.join("state.sql")).await;
+    DBHandler::new(chain, local, cache)
+}
+
+/// shared state without node endpoints
+pub fn shared(db: DBHandler, config: Config) -> Arc<Shared> {
+    let rpc_handler = RpcHandler::new(vec![], ApiVersion::V1).unwrap();
+    Shared::new(db, rpc_handler, config)
+}
@@ -0,0 +1,168 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Query,
+    },
+    http::{header, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use poolstats::error::{ApiError, ApiResponse, Partial};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn decode(response: Response) -> (StatusCode, Value) {
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[derive(Deserialize)]
+struct Params {
+    epoch: i64,
+}
+
+async fn with_json(req: Result<Json<Params>, JsonRejection>) -> Result<ApiResponse<i64>, ApiError> {
+    let Json(params) = req?;
+    Ok(ApiResponse::new(params.epoch, false))
+}
+
+async fn with_query(
+    query: Result<Query<Params>, QueryRejection>,
+) -> Result<ApiResponse<i64>, ApiError> {
+    let Query(params) = query?;
+    Ok(ApiResponse::new(params.epoch, false))
+}
+
+fn router() -> Router {
+    Router::new()
+        .route("/json", post(with_json))
+        .route("/query", get(with_query))
+}
+
+#[tokio::test]
+async fn errors_carry_their_status_and_code() {
+    let cases = [
+        (
+            ApiError::bad_request("invalid_cursor", "invalid cursor"),
+            StatusCode::BAD_REQUEST,
+            "invalid_cursor",
+            "invalid cursor",
+        ),
+        (
+            ApiError::new(StatusCode::NOT_FOUND, "epoch_not_found", "no data"),
+            StatusCode::NOT_FOUND,
+            "epoch_not_found",
+            "no data",
+        ),
+        (
+            ApiError::timeout(),
+            StatusCode::REQUEST_TIMEOUT,
+            "timeout",
+            "request timed out",
+        ),
+        (
+            ApiError::rpc("connection refused by 10.0.0.5:9093"),
+            StatusCode::BAD_GATEWAY,
+            "rpc_unavailable",
+            "node rpc unavailable",
+        ),
+    ];
+    for (error, status, code, message) in cases {
+        assert_eq!(error.to_string(), format!("{}: {}", code, message));
+        assert_eq!(
+            decode(error.into_response()).await,
+            (
+                status,
+                json!({"code": status.as_u16(), "error": code, "message": message})
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn database_errors_do_not_leak_details() {
+    let error = ApiError::from(sqlx::Error::Protocol("no such table: atxs".to_string()));
+    let (status, body) = decode(error.into_response()).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        body,
+        json!({"code": 503, "error": "database_unavailable", "message": "database unavailable"})
+    );
+}
+
+#[tokio::test]
+async fn rejections_are_structured() {
+    let request = Request::post("/json")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{\"epoch\":"))
+        .unwrap();
+    let (status, body) = decode(router().oneshot(request).await.unwrap()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_request");
+    assert_eq!(body["code"], 400);
+
+    let request = Request::post("/json")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{\"epoch\":\"ten\"}"))
+        .unwrap();
+    let (status, body) = decode(router().oneshot(request).await.unwrap()).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"], "invalid_request");
+
+    let request = Request::post("/json").body(Body::from("{}")).unwrap();
+    let (status, body) = decode(router().oneshot(request).await.unwrap()).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(body["error"], "invalid_request");
+
+    let request = Request::get("/query?epoch=10").body(Body::empty()).unwrap();
+    let (status, body) = decode(router().oneshot(request).await.unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"code": 200, "data": 10, "partial": false}));
+
+    let request = Request::get("/query?epoch=ten")
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = decode(router().oneshot(request).await.unwrap()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_query");
+    assert!(body["message"]
+        .as_str()
+        .is_some_and(|message| !message.is_empty()));
+}
+
+#[tokio::test]
+async fn responses_are_enveloped() {
+    let (status, body) = decode(ApiResponse::new(json!({"epoch": 9}), false).into_response()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({"code": 200, "data": {"epoch": 9}, "partial": false})
+    );
+
+    let (_, body) = decode(ApiResponse::new(vec![1, 2], true).into_response()).await;
+    assert_eq!(body, json!({"code": 200, "data": [1, 2], "partial": true}));
+}
+
+#[test]
+fn partial_tracks_failed_checks() {
+    let mut partial = Partial::default();
+    assert!(!partial.is_partial());
+    assert!(!partial.all_failed());
+
+    assert_eq!(partial.check(Ok::<i64, String>(7)), 7);
+    assert!(!partial.is_partial());
+
+    let failed: Vec<i64> = partial.check(Err("locked".to_string()));
+    assert!(failed.is_empty());
+    assert!(partial.is_partial());
+    assert!(!partial.all_failed());
+
+    let mut partial = Partial::default();
+    assert_eq!(partial.check(Err::<i64, _>("locked")), 0);
+    assert_eq!(partial.check(Err::<String, _>("locked")), "");
+    assert!(partial.all_failed());
+}
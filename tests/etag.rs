@@ -0,0 +1,246 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware,
+    response::Response,
+    routing::{get, post},
+    Router,
+};
+use poolstats::{
+    clock::{unix_now, ClockState},
+    config::Config,
+    etag::{conditional, Depends},
+    rpc::NodeState,
+    Shared,
+};
+use tower::ServiceExt;
+
+struct Fixture {
+    shared: Arc<Shared>,
+    router: Router,
+    calls: Arc<AtomicUsize>,
+}
+
+/// `/timeline` depends on the cache only, `/keys` also on `post` and registrations and
+/// `/overview` on `post` and the node
+async fn fixture(name: &str) -> Fixture {
+    let keys = [([1u8; 32].to_vec(), 4)];
+    let shared = common::shared(common::databases(name, &keys).await, Config::default());
+    let calls = Arc::new(AtomicUsize::new(0));
+    let handler = {
+        let calls = calls.clone();
+        move || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            "data"
+        }
+    };
+    let tagged = |depends| middleware::from_fn_with_state((shared.clone(), depends), conditional);
+    let keys = Depends {
+        epoch: true,
+        post: true,
+        registrations: true,
+        node: false,
+    };
+    let overview = Depends {
+        registrations: false,
+        node: true,
+        ..keys
+    };
+    let router = Router::new()
+        .route(
+            "/timeline",
+            get(handler.clone()).route_layer(tagged(Depends::default())),
+        )
+        .route("/keys", post(handler.clone()).route_layer(tagged(keys)))
+        .route("/overview", get(handler).route_layer(tagged(overview)))
+        .route(
+            "/missing",
+            get(|| async { StatusCode::NOT_FOUND }).route_layer(tagged(keys)),
+        );
+    Fixture {
+        shared,
+        router,
+        calls,
+    }
+}
+
+impl Fixture {
+    async fn send(&self, method: &str, path: &str, body: &str, tag: Option<&str>) -> Response {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(tag) = tag {
+            request = request.header(header::IF_NONE_MATCH, tag);
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    async fn tag(&self, method: &str, path: &str, body: &str) -> String {
+        let response = self.send(method, path, body, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        etag(&response).unwrap()
+    }
+}
+
+fn etag(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(header::ETAG)
+        .map(|tag| tag.to_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn answers_a_matching_tag_with_304() {
+    let fixture = fixture("etag-match").await;
+    // nothing stored yet
+    let response = fixture.send("GET", "/timeline", "", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(etag(&response), None);
+
+    fixture.shared.db_handler.bump_generation().await.unwrap();
+    let tag = fixture.tag("GET", "/timeline", "").await;
+    assert!(tag.starts_with("W/\"1-"), "{}", tag);
+    let calls = fixture.calls.load(Ordering::SeqCst);
+
+    let strong = tag.trim_start_matches("W/").to_string();
+    for presented in [
+        tag.clone(),
+        strong,
+        format!("\"other\", {}", tag),
+        "*".to_string(),
+    ] {
+        let response = fixture.send("GET", "/timeline", "", Some(&presented)).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", presented);
+        assert_eq!(etag(&response), Some(tag.clone()));
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL),
+            Some(&HeaderValue::from_static("no-cache"))
+        );
+    }
+    // 304s never reach the handler
+    assert_eq!(fixture.calls.load(Ordering::SeqCst), calls);
+
+    let response = fixture
+        .send("GET", "/timeline", "", Some("W/\"1-0000000000000000\""))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // every write to the cache changes the tag
+    fixture.shared.db_handler.bump_generation().await.unwrap();
+    let response = fixture.send("GET", "/timeline", "", Some(&tag)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(etag(&response), Some(tag));
+
+    let response = fixture.send("GET", "/missing", "", None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(etag(&response), None);
+}
+
+#[tokio::test]
+async fn tags_the_request() {
+    let fixture = fixture("etag-request").await;
+    fixture.shared.db_handler.bump_generation().await.unwrap();
+    let first = fixture.tag("POST", "/keys", "{\"limit\":1}").await;
+    assert_eq!(fixture.tag("POST", "/keys", "{\"limit\":1}").await, first);
+    assert_ne!(fixture.tag("POST", "/keys", "{\"limit\":2}").await, first);
+    assert_ne!(
+        fixture.tag("POST", "/keys?x=1", "{\"limit\":1}").await,
+        first
+    );
+}
+
+#[tokio::test]
+async fn follows_what_each_route_depends_on() {
+    let fixture = fixture("etag-depends").await;
+    fixture.shared.db_handler.bump_generation().await.unwrap();
+    let tags = || async {
+        (
+            fixture.tag("GET", "/timeline", "").await,
+            fixture.tag("POST", "/keys", "").await,
+            fixture.tag("GET", "/overview", "").await,
+        )
+    };
+    let (timeline, keys, overview) = tags().await;
+
+    // a key initialized on the node
+    sqlx::query("INSERT INTO post (id, num_units) VALUES ($1, 8)")
+        .bind([2u8; 32].to_vec())
+        .execute(&fixture.shared.db_handler.local)
+        .await
+        .unwrap();
+    let (next_timeline, next_keys, next_overview) = tags().await;
+    assert_eq!(next_timeline, timeline);
+    assert_ne!(next_keys, keys);
+    assert_ne!(next_overview, overview);
+    let (keys, overview) = (next_keys, next_overview);
+
+    // a new epoch
+    fixture.shared.clock.set(ClockState {
+        epoch: 10,
+        layer: 40320,
+        refreshed_at: unix_now(),
+    });
+    let (next_timeline, next_keys, next_overview) = tags().await;
+    assert_eq!(next_timeline, timeline);
+    assert_ne!(next_keys, keys);
+    assert_ne!(next_overview, overview);
+    let (keys, overview) = (next_keys, next_overview);
+
+    // a new layer on the node only matters to the overview, its peers to none
+    let mut state = NodeState {
+        connected_peers: 8,
+        synced: true,
+        synced_layer: 40320,
+        top_layer: 40320,
+        verified_layer: 40319,
+    };
+    fixture.shared.node.set(state.clone());
+    let (_, _, overview_synced) = tags().await;
+    assert_ne!(overview_synced, overview);
+    state.connected_peers = 9;
+    fixture.shared.node.set(state.clone());
+    assert_eq!(
+        tags().await,
+        (timeline.clone(), keys.clone(), overview_synced.clone())
+    );
+    state.top_layer += 1;
+    fixture.shared.node.set(state);
+    let (next_timeline, next_keys, next_overview) = tags().await;
+    assert_eq!((next_timeline, next_keys), (timeline.clone(), keys.clone()));
+    assert_ne!(next_overview, overview_synced);
+    let overview = next_overview;
+
+    // the node registers with a poet, only nodes_info reads that live
+    sqlx::query("INSERT INTO poet_registration (id, hash, address, round_id, round_end) VALUES ($1, $2, 'poet', '10', 100)")
+        .bind([1u8; 32].to_vec())
+        .bind([3u8; 32].to_vec())
+        .execute(&fixture.shared.db_handler.local)
+        .await
+        .unwrap();
+    let (next_timeline, next_keys, next_overview) = tags().await;
+    assert_eq!(
+        (next_timeline, next_overview),
+        (timeline.clone(), overview.clone())
+    );
+    assert_ne!(next_keys, keys);
+    let keys = next_keys;
+
+    // the registration window opens mid-epoch and nodes_info switches to the next round
+    let network = &fixture.shared.config.network;
+    let open = ClockState {
+        epoch: 10,
+        layer: 10 * network.layers_per_epoch + network.poet_registration_offset,
+        refreshed_at: unix_now(),
+    };
+    assert!(open.registration_open(network));
+    fixture.shared.clock.set(open);
+    let (next_timeline, next_keys, next_overview) = tags().await;
+    assert_eq!((next_timeline, next_overview), (timeline, overview));
+    assert_ne!(next_keys, keys);
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use futures::StreamExt;
+use poolstats::{
+    config::Config,
+    export::{export_lines, export_rows, ExportFormat, ExportRow, EXPORT_BATCH},
+    poolstats::AtxInfo,
+};
+
+const EPOCH: i64 = 9;
+
+fn row() -> ExportRow {
+    ExportRow {
+        id: "0a".repeat(32),
+        num_units: 4,
+        round_id: Some("9".to_string()),
+        poet_address: Some("https://poet.example".to_string()),
+        atx_id: Some("0b".repeat(32)),
+        effective_num_units: Some(4),
+        coinbase: Some("0c".repeat(24)),
+    }
+}
+
+#[test]
+fn escapes_csv_fields() {
+    let plain = row();
+    assert_eq!(
+        plain.to_csv(),
+        format!(
+            "{},4,9,https://poet.example,{},4,{}",
+            "0a".repeat(32),
+            "0b".repeat(32),
+            "0c".repeat(24)
+        )
+    );
+
+    let cases = [
+        ("https://poet.example/a,b", "\"https://poet.example/a,b\""),
+        ("say \"hi\"", "\"say \"\"hi\"\"\""),
+        ("two\nlines", "\"two\nlines\""),
+        ("carriage\rreturn", "\"carriage\rreturn\""),
+        ("", ""),
+    ];
+    for (address, escaped) in cases {
+        let row = ExportRow {
+            poet_address: Some(address.to_string()),
+            ..row()
+        };
+        let csv = row.to_csv();
+        assert_eq!(
+            csv.split(',').nth(3),
+            escaped.split(',').next(),
+            "{}",
+            address
+        );
+        assert!(csv.contains(escaped), "{}", csv);
+    }
+
+    let missing = ExportRow {
+        round_id: None,
+        poet_address: None,
+        atx_id: None,
+        effective_num_units: None,
+        coinbase: None,
+        ..row()
+    };
+    assert_eq!(missing.to_csv(), format!("{},4,,,,,", "0a".repeat(32)));
+    assert_eq!(
+        ExportRow::CSV_HEADER.split(',').count(),
+        missing.to_csv().split(',').count()
+    );
+}
+
+#[test]
+fn renders_each_format() {
+    let row = ExportRow {
+        poet_address: Some("a,\"b\"".to_string()),
+        ..row()
+    };
+    assert_eq!(
+        ExportFormat::Csv.header().unwrap(),
+        format!("{}\n", ExportRow::CSV_HEADER)
+    );
+    assert_eq!(ExportFormat::Csv.line(&row), format!("{}\n", row.to_csv()));
+
+    assert_eq!(ExportFormat::Ndjson.header(), None);
+    let line = ExportFormat::Ndjson.line(&row);
+    assert!(line.ends_with('\n') && !line.trim_end().contains('\n'));
+    assert_eq!(
+        serde_json::from_str::<ExportRow>(line.trim_end()).unwrap(),
+        row
+    );
+}
+
+#[tokio::test]
+async fn pages_through_every_key() {
+    // a full page, a second full page and one key more
+    let count = 2 * EXPORT_BATCH as u32 + 1;
+    let keys: Vec<_> = (0..count)
+        .map(|i| (i.to_be_bytes().repeat(8), i as i64 % 7 + 1))
+        .collect();
+    let db = common::databases("export", &keys).await;
+    for i in [0u32, EXPORT_BATCH as u32, count - 1] {
+        let id = hex::encode(i.to_be_bytes().repeat(8));
+        sqlx::query("INSERT INTO poet_registration (id, hash, address, round_id, round_end) VALUES ($1, x'00', 'https://poet.example', $2, 0)")
+            .bind(hex::decode(&id).unwrap())
+            .bind(EPOCH.to_string())
+            .execute(&db.local)
+            .await
+            .unwrap();
+        let atx = AtxInfo {
+            epoch: EPOCH,
+            atx_id: hex::encode(i.wrapping_add(1).to_le_bytes().repeat(8)),
+            effective_num_units: 2,
+            coinbase: hex::encode([2; 24]),
+        };
+        db.save_atx(id, 2, atx).await.unwrap();
+    }
+    let shared = common::shared(db, Config::default());
+
+    let rows: Vec<ExportRow> = export_rows(shared.clone(), EPOCH)
+        .map(|row| row.unwrap())
+        .collect()
+        .await;
+    let want: Vec<_> = keys.iter().map(|(id, _)| hex::encode(id)).collect();
+    assert_eq!(
+        rows.iter().map(|row| row.id.clone()).collect::<Vec<_>>(),
+        want
+    );
+    for (i, row) in rows.iter().enumerate() {
+        let known = [0, EXPORT_BATCH as usize, count as usize - 1].contains(&i);
+        assert_eq!(row.num_units, keys[i].1);
+        assert_eq!(row.round_id.is_some(), known, "{}", i);
+        assert_eq!(row.effective_num_units, known.then_some(2), "{}", i);
+    }
+
+    let lines: Vec<String> = export_lines(shared.clone(), EPOCH, ExportFormat::Csv)
+        .map(|line| line.unwrap())
+        .collect()
+        .await;
+    assert_eq!(lines.len(), count as usize + 1);
+    assert_eq!(lines[0], format!("{}\n", ExportRow::CSV_HEADER));
+
+    // a broken cursor fails instead of starting over
+    let db = &shared.db_handler;
+    let after = db.get_init_keys(1, Some(hex::encode(&keys[0].0))).await;
+    assert_eq!(after.unwrap()[0].id, hex::encode(&keys[1].0));
+    assert!(matches!(
+        db.get_init_keys(1, Some("zz".to_string())).await,
+        Err(sqlx::Error::Decode(_))
+    ));
+}
@@ -0,0 +1,226 @@
+use poolstats::rpc::{
+    grpc::proto::{
+        mesh_service_server::{MeshService, MeshServiceServer},
+        node_service_server::{NodeService, NodeServiceServer},
+        post_setup_status::State,
+        smesher_service_server::{SmesherService, SmesherServiceServer},
+        AccountId, CoinbaseResponse, CurrentEpochRequest, CurrentEpochResponse,
+        CurrentLayerRequest, CurrentLayerResponse, EpochNumLayersRequest, EpochNumLayersResponse,
+        EpochNumber, GenesisTimeRequest, GenesisTimeResponse, IsSmeshingResponse,
+        LayerDurationRequest, LayerDurationResponse, LayerNumber, NodeStatus, PostSetupOpts,
+        PostSetupStatus, PostSetupStatusResponse, SimpleInt, SmesherIDsResponse, StatusRequest,
+        StatusResponse,
+    },
+    ApiVersion, NetworkInfo, NodeEndpoint, NodeState, PostSetup, PostSetupState, RpcHandler,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+const SMESHER: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+/// answers like a node in epoch 10 that is still initializing its post data
+struct StandIn;
+
+fn layer(number: u32) -> Option<LayerNumber> {
+    Some(LayerNumber { number })
+}
+
+#[tonic::async_trait]
+impl MeshService for StandIn {
+    async fn genesis_time(
+        &self,
+        _: Request<GenesisTimeRequest>,
+    ) -> Result<Response<GenesisTimeResponse>, Status> {
+        Ok(Response::new(GenesisTimeResponse {
+            unixtime: Some(SimpleInt { value: 1689321600 }),
+        }))
+    }
+
+    async fn current_layer(
+        &self,
+        _: Request<CurrentLayerRequest>,
+    ) -> Result<Response<CurrentLayerResponse>, Status> {
+        Ok(Response::new(CurrentLayerResponse {
+            layernum: layer(40400),
+        }))
+    }
+
+    async fn current_epoch(
+        &self,
+        _: Request<CurrentEpochRequest>,
+    ) -> Result<Response<CurrentEpochResponse>, Status> {
+        Ok(Response::new(CurrentEpochResponse {
+            epochnum: Some(EpochNumber { number: 10 }),
+        }))
+    }
+
+    async fn epoch_num_layers(
+        &self,
+        _: Request<EpochNumLayersRequest>,
+    ) -> Result<Response<EpochNumLayersResponse>, Status> {
+        Ok(Response::new(EpochNumLayersResponse {
+            numlayers: layer(4032),
+        }))
+    }
+
+    async fn layer_duration(
+        &self,
+        _: Request<LayerDurationRequest>,
+    ) -> Result<Response<LayerDurationResponse>, Status> {
+        Ok(Response::new(LayerDurationResponse {
+            duration: Some(SimpleInt { value: 300 }),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl NodeService for StandIn {
+    async fn status(&self, _: Request<StatusRequest>) -> Result<Response<StatusResponse>, Status> {
+        Ok(Response::new(StatusResponse {
+            status: Some(NodeStatus {
+                connected_peers: 12,
+                is_synced: true,
+                synced_layer: layer(40400),
+                top_layer: layer(40401),
+                verified_layer: layer(40399),
+            }),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl SmesherService for StandIn {
+    async fn is_smeshing(&self, _: Request<()>) -> Result<Response<IsSmeshingResponse>, Status> {
+        Ok(Response::new(IsSmeshingResponse { is_smeshing: true }))
+    }
+
+    async fn smesher_i_ds(&self, _: Request<()>) -> Result<Response<SmesherIDsResponse>, Status> {
+        Ok(Response::new(SmesherIDsResponse {
+            public_keys: vec![vec![1; 32]],
+        }))
+    }
+
+    async fn coinbase(&self, _: Request<()>) -> Result<Response<CoinbaseResponse>, Status> {
+        Ok(Response::new(CoinbaseResponse {
+            account_id: Some(AccountId {
+                address: "sm1qqqqqqexample".to_string(),
+            }),
+        }))
+    }
+
+    async fn post_setup_status(
+        &self,
+        _: Request<()>,
+    ) -> Result<Response<PostSetupStatusResponse>, Status> {
+        Ok(Response::new(PostSetupStatusResponse {
+            status: Some(PostSetupStatus {
+                state: State::InProgress as i32,
+                num_labels_written: 1024,
+                opts: Some(PostSetupOpts {
+                    data_dir: "/data/post".to_string(),
+                    num_units: 4,
+                    max_file_size: 4294967296,
+                }),
+            }),
+        }))
+    }
+}
+
+/// serves the stand-in on a free local port and returns its `grpc://` endpoint
+async fn stand_in() -> NodeEndpoint {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(MeshServiceServer::new(StandIn))
+            .add_service(NodeServiceServer::new(StandIn))
+            .add_service(SmesherServiceServer::new(StandIn))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .unwrap()
+    });
+    format!("grpc://{}", addr).parse().unwrap()
+}
+
+#[tokio::test]
+async fn grpc_node() {
+    let rpc = RpcHandler::new(vec![stand_in().await], ApiVersion::Auto).unwrap();
+    assert_eq!(rpc.api_version().await.unwrap(), ApiVersion::V1);
+    assert_eq!(rpc.get_epoch().await.unwrap().epochnum.number, 10);
+    assert_eq!(rpc.get_layer().await.unwrap().layernum.number, 40400);
+    assert_eq!(
+        rpc.network_info().await.unwrap(),
+        NetworkInfo {
+            layers_per_epoch: 4032,
+            layer_duration: 300,
+        }
+    );
+    assert_eq!(
+        rpc.node_status().await.unwrap(),
+        NodeState {
+            connected_peers: 12,
+            synced: true,
+            synced_layer: 40400,
+            top_layer: 40401,
+            verified_layer: 40399,
+        }
+    );
+}
+
+#[tokio::test]
+async fn grpc_smesher() {
+    let rpc = RpcHandler::new(vec![stand_in().await], ApiVersion::V1).unwrap();
+    assert!(rpc.is_smeshing().await.unwrap());
+    assert_eq!(rpc.smesher_ids().await.unwrap(), vec![SMESHER.to_string()]);
+    assert_eq!(rpc.coinbase().await.unwrap(), "sm1qqqqqqexample");
+    assert_eq!(
+        rpc.post_setup_status().await.unwrap(),
+        PostSetup {
+            state: PostSetupState::InProgress,
+            num_labels_written: 1024,
+            num_units: 4,
+        }
+    );
+}
+
+#[tokio::test]
+async fn grpc_rejects_v2alpha1() {
+    let rpc = RpcHandler::new(vec![stand_in().await], ApiVersion::V2alpha1).unwrap();
+    assert!(rpc.api_version().await.is_err());
+    assert!(rpc.get_layer().await.is_err());
+}
+
+#[tokio::test]
+async fn grpc_node_down() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let rpc = RpcHandler::new(
+        vec![format!("grpc://{}", addr).parse().unwrap()],
+        ApiVersion::V1,
+    )
+    .unwrap();
+    assert!(rpc.get_epoch().await.is_err());
+}
+
+#[test]
+fn parses_endpoints() {
+    let parse = |s: &str| s.parse::<NodeEndpoint>().unwrap();
+    assert_eq!(
+        parse("127.0.0.1:9093"),
+        NodeEndpoint::Gateway("http://127.0.0.1:9093".to_string())
+    );
+    assert_eq!(
+        parse("https://node.example/"),
+        NodeEndpoint::Gateway("https://node.example".to_string())
+    );
+    assert_eq!(
+        parse("grpc://127.0.0.1:9092").to_string(),
+        "grpc://127.0.0.1:9092"
+    );
+    assert_eq!(
+        parse("grpcs://node.example:9092").to_string(),
+        "grpcs://node.example:9092"
+    );
+}
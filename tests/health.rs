@@ -0,0 +1,89 @@
+mod common;
+
+use std::time::Duration;
+
+use poolstats::{
+    clock::{unix_now, ClockState},
+    config::Config,
+    health::{check_clock, check_node, readiness, ComponentStatus, MISSED_REFRESHES},
+    node::NodeStatusInfo,
+    rpc::NodeState,
+};
+
+const INTERVAL: Duration = Duration::from_secs(10);
+
+fn state(synced: bool) -> NodeState {
+    NodeState {
+        connected_peers: 8,
+        synced,
+        synced_layer: 40320,
+        top_layer: 40321,
+        verified_layer: 40319,
+    }
+}
+
+fn clock(age: i64) -> ClockState {
+    ClockState {
+        epoch: 10,
+        layer: 40321,
+        refreshed_at: unix_now() - age,
+    }
+}
+
+#[test]
+fn judges_the_cached_clock() {
+    let component = check_clock(None, INTERVAL);
+    assert_eq!(component.status, ComponentStatus::Error);
+    assert_eq!(
+        component.message.as_deref(),
+        Some("chain clock not refreshed yet")
+    );
+
+    let component = check_clock(Some(clock(5)), INTERVAL);
+    assert_eq!(component.status, ComponentStatus::Ok);
+    assert_eq!(component.details["layer"], 40321);
+
+    let max_age = MISSED_REFRESHES * INTERVAL.as_secs() as i64;
+    assert!(check_clock(Some(clock(max_age)), INTERVAL).is_ok());
+    let component = check_clock(Some(clock(max_age + 1)), INTERVAL);
+    assert_eq!(component.status, ComponentStatus::Error);
+    assert_eq!(component.details["age"], max_age + 1);
+}
+
+#[test]
+fn judges_the_cached_node_status() {
+    let info = |synced, age| NodeStatusInfo {
+        state: state(synced),
+        refreshed_at: unix_now() - age,
+    };
+    assert_eq!(check_node(None, INTERVAL).status, ComponentStatus::Error);
+    let component = check_node(Some(info(true, 0)), INTERVAL);
+    assert_eq!(component.status, ComponentStatus::Ok);
+    assert_eq!(component.details["connected_peers"], 8);
+    assert_eq!(
+        check_node(Some(info(false, 0)), INTERVAL).status,
+        ComponentStatus::Warning
+    );
+    let component = check_node(Some(info(true, 3600)), INTERVAL);
+    assert_eq!(component.status, ComponentStatus::Error);
+    assert_eq!(
+        component.message.as_deref(),
+        Some("node status last refreshed 3600s ago")
+    );
+}
+
+#[tokio::test]
+async fn answers_without_the_node() {
+    // no endpoints, so any rpc would fail
+    let shared = common::shared(common::databases("readiness", &[]).await, Config::default());
+    let ready = readiness(&shared).await;
+    assert!(!ready.ready);
+    assert_eq!(ready.components["rpc"].status, ComponentStatus::Error);
+    assert_eq!(ready.components["node"].status, ComponentStatus::Error);
+
+    shared.clock.set(clock(0));
+    shared.node.set(state(true));
+    let ready = readiness(&shared).await;
+    assert_eq!(ready.components["rpc"].status, ComponentStatus::Ok);
+    assert_eq!(ready.components["node"].status, ComponentStatus::Ok);
+}
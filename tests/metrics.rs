@@ -0,0 +1,76 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::{body::to_bytes, extract::State};
+use poolstats::{
+    clock::{unix_now, ClockState},
+    config::Config,
+    metrics::{metrics_handler, TrackDbError},
+    rpc::{ApiVersion, RpcHandler},
+    Shared,
+};
+
+async fn scrape(shared: &Arc<Shared>) -> String {
+    let response = metrics_handler(State(shared.clone())).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+/// value of the sample named exactly `series`, labels included
+fn sample(metrics: &str, series: &str) -> Option<f64> {
+    metrics.lines().find_map(|line| {
+        let (name, value) = line.rsplit_once(' ')?;
+        (name == series).then(|| value.parse().unwrap())
+    })
+}
+
+#[tokio::test]
+async fn exposes_overview_rpc_and_db_metrics() {
+    let keys = [([1u8; 32].to_vec(), 4), ([2u8; 32].to_vec(), 8)];
+    let db = common::databases("metrics", &keys).await;
+    // a node nothing listens on
+    let rpc = RpcHandler::new(vec!["127.0.0.1:1".parse().unwrap()], ApiVersion::V1).unwrap();
+    let shared = Shared::new(db, rpc, Config::default());
+    shared.clock.set(ClockState {
+        epoch: 11,
+        layer: 11 * 4032,
+        refreshed_at: unix_now(),
+    });
+
+    let before = scrape(&shared).await;
+    assert_eq!(sample(&before, "poolstats_initialized_count"), Some(2.0));
+    assert_eq!(
+        sample(&before, "poolstats_initialized_num_units"),
+        Some(12.0)
+    );
+    assert_eq!(
+        sample(&before, "poolstats_registered_count{epoch=\"next\"}"),
+        Some(0.0)
+    );
+    assert_eq!(
+        sample(&before, "poolstats_active_count{epoch=\"current\"}"),
+        Some(0.0)
+    );
+    assert_eq!(sample(&before, "poolstats_current_epoch"), Some(11.0));
+
+    assert!(shared.rpc_handler.node_status().await.is_err());
+    let failed = sqlx::query("SELECT * FROM missing")
+        .execute(&shared.db_handler.local)
+        .await
+        .track("local");
+    assert!(failed.is_err());
+
+    let after = scrape(&shared).await;
+    let requests = "poolstats_rpc_request_duration_seconds_count{method=\"node_status\"}";
+    assert!(sample(&after, requests).unwrap() >= 1.0, "{}", after);
+    let errors = "poolstats_rpc_errors_total{method=\"node_status\"}";
+    assert!(sample(&after, errors).unwrap() >= 1.0, "{}", after);
+    // other tests in this process may fail queries too, so only the increase is known
+    let db_errors = "poolstats_db_errors_total{pool=\"local\"}";
+    assert!(
+        sample(&after, db_errors).unwrap() >= sample(&before, db_errors).unwrap() + 1.0,
+        "{}",
+        after
+    );
+}
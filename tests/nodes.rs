@@ -0,0 +1,227 @@
+mod common;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use poolstats::{
+    poolstats::{
+        AtxInfo, Cursor, GeneralRequest, Key, NodeStatus, Registeration, SortBy, SortOrder,
+    },
+    DBHandler,
+};
+
+const EPOCH: i64 = 9;
+const ROUND: &str = "9";
+
+/// (num_units, effective_num_units, registered) of keys 1 to 12, with plenty of ties
+fn key(i: u8) -> (i64, Option<i64>, bool) {
+    let num_units = 4 * (i as i64 % 3 + 1);
+    let effective = (i % 4 < 2).then_some(num_units / 2 * (i as i64 % 2 + 1));
+    (num_units, effective, i % 4 != 3)
+}
+
+fn id(i: u8) -> String {
+    hex::encode([i; 32])
+}
+
+/// a node's local db with keys 1 to 12 and a migrated cache holding their atxs and registrations
+async fn databases(name: &str) -> DBHandler {
+    let keys: Vec<_> = (1..=12).map(|i| ([i; 32].to_vec(), key(i).0)).collect();
+    let db = common::databases(name, &keys).await;
+    for i in 1..=12 {
+        let (num_units, effective, registered) = key(i);
+        if registered {
+            let registeration = Registeration {
+                address: "https://poet.example".to_string(),
+                round_id: ROUND.to_string(),
+                round_end: 0,
+            };
+            db.save_poet(id(i), num_units, registeration).await.unwrap();
+        }
+        if let Some(effective_num_units) = effective {
+            let atx = AtxInfo {
+                epoch: EPOCH,
+                atx_id: hex::encode([i + 100; 32]),
+                effective_num_units,
+                coinbase: hex::encode([i % 2; 24]),
+            };
+            db.save_atx(id(i), num_units, atx).await.unwrap();
+        }
+    }
+    db
+}
+
+/// ids of every page of `limit` nodes, following the cursor of each
+async fn page_through(db: &DBHandler, req: &GeneralRequest, limit: i64) -> Vec<String> {
+    let mut ids = vec![];
+    let mut cursor = None;
+    loop {
+        let mut page = db
+            .get_nodes_page(req, EPOCH, ROUND, cursor.as_ref(), limit + 1)
+            .await
+            .unwrap();
+        let more = page.len() as i64 > limit;
+        page.truncate(limit as usize);
+        cursor = page.last().map(|(key, _, atx)| {
+            Cursor::decode(&Cursor::of(req.sort_by, key, atx).encode()).unwrap()
+        });
+        ids.extend(page.into_iter().map(|(key, _, _)| key.id));
+        if !more {
+            return ids;
+        }
+    }
+}
+
+/// ids of keys 1 to 12 in the order `sort_by` and `order` ask for
+fn expected(sort_by: SortBy, order: SortOrder, keep: impl Fn(u8) -> bool) -> Vec<String> {
+    let mut keys: Vec<(i64, String)> = (1..=12)
+        .filter(|i| keep(*i))
+        .map(|i| {
+            let (num_units, effective, _) = key(i);
+            let value = match sort_by {
+                SortBy::Id => 0,
+                SortBy::NumUnits => num_units,
+                SortBy::EffectiveNumUnits => effective.unwrap_or_default(),
+            };
+            (value, id(i))
+        })
+        .collect();
+    keys.sort();
+    if order == SortOrder::Desc {
+        keys.reverse();
+    }
+    keys.into_iter().map(|(_, id)| id).collect()
+}
+
+#[test]
+fn cursor_round_trips() {
+    for sort_by in [SortBy::Id, SortBy::NumUnits, SortBy::EffectiveNumUnits] {
+        let cursor = Cursor {
+            sort_by,
+            value: -7,
+            id: id(3),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+    }
+    let key = Key {
+        id: id(5),
+        num_units: 8,
+    };
+    let atx = AtxInfo {
+        effective_num_units: 16,
+        ..Default::default()
+    };
+    assert_eq!(Cursor::of(SortBy::NumUnits, &key, &atx).value, 8);
+    assert_eq!(Cursor::of(SortBy::EffectiveNumUnits, &key, &atx).value, 16);
+    assert_eq!(Cursor::of(SortBy::Id, &key, &atx).value, 0);
+}
+
+#[test]
+fn rejects_malformed_cursors() {
+    let encode = |raw: &str| URL_SAFE_NO_PAD.encode(raw);
+    for cursor in [
+        "not base64!".to_string(),
+        URL_SAFE_NO_PAD.encode([0xff, 0xfe]),
+        encode(""),
+        encode("num_units"),
+        encode("num_units:4"),
+        encode("size:4:0101"),
+        encode("num_units:four:0101"),
+        encode("num_units:4:not hex"),
+        encode("num_units:4:0101' OR 1 = 1 --"),
+    ] {
+        assert_eq!(Cursor::decode(&cursor), None, "{}", cursor);
+    }
+}
+
+#[tokio::test]
+async fn pages_through_ties_in_every_order() {
+    let db = databases("pages").await;
+    for sort_by in [SortBy::Id, SortBy::NumUnits, SortBy::EffectiveNumUnits] {
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let req = GeneralRequest {
+                sort_by,
+                order,
+                ..Default::default()
+            };
+            let want = expected(sort_by, order, |_| true);
+            for limit in [1, 2, 5, 12, 20] {
+                assert_eq!(
+                    page_through(&db, &req, limit).await,
+                    want,
+                    "{:?} {:?} by {}",
+                    sort_by,
+                    order,
+                    limit
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn filters_and_counts_in_the_query() {
+    let db = databases("filters").await;
+    let cases = [
+        (Some(NodeStatus::Active), None),
+        (Some(NodeStatus::Registered), None),
+        (Some(NodeStatus::Missing), None),
+        (None, Some(hex::encode([1u8; 24]))),
+        (
+            Some(NodeStatus::Active),
+            Some(hex::encode([0u8; 24]).to_uppercase()),
+        ),
+    ];
+    for (status, coinbase) in cases {
+        let keep = |i: u8| {
+            let (_, effective, registered) = key(i);
+            let actual = match (effective.is_some(), registered) {
+                (true, _) => NodeStatus::Active,
+                (false, true) => NodeStatus::Registered,
+                (false, false) => NodeStatus::Missing,
+            };
+            status.is_none_or(|status| status == actual)
+                && coinbase.as_ref().is_none_or(|coinbase| {
+                    effective.is_some() && *coinbase.to_lowercase() == hex::encode([i % 2; 24])
+                })
+        };
+        let req = GeneralRequest {
+            status,
+            coinbase: coinbase.clone(),
+            sort_by: SortBy::NumUnits,
+            ..Default::default()
+        };
+        let want = expected(SortBy::NumUnits, SortOrder::Asc, keep);
+        assert!(!want.is_empty());
+        assert_eq!(
+            page_through(&db, &req, 2).await,
+            want,
+            "{:?} {:?}",
+            status,
+            coinbase
+        );
+        let total = db.count_nodes(&req, EPOCH, ROUND).await.unwrap();
+        assert_eq!(total as usize, want.len());
+    }
+
+    let req = GeneralRequest {
+        min_num_units: Some(12),
+        max_num_units: Some(12),
+        id_prefix: Some("0B".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(page_through(&db, &req, 5).await, [id(11)]);
+}
+
+#[tokio::test]
+async fn offset_without_a_cursor() {
+    let db = databases("offset").await;
+    let req = GeneralRequest {
+        offset: 10,
+        ..Default::default()
+    };
+    let page = db
+        .get_nodes_page(&req, EPOCH, ROUND, None, 5)
+        .await
+        .unwrap();
+    let ids: Vec<_> = page.into_iter().map(|(key, _, _)| key.id).collect();
+    assert_eq!(ids, [id(11), id(12)]);
+}
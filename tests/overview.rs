@@ -0,0 +1,131 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use poolstats::{
+    clock::{unix_now, ClockState},
+    config::Config,
+    poolstats::{overview_handler, AtxInfo, OverviewQuery, Registeration},
+    rpc::NodeState,
+    Shared,
+};
+use serde_json::{json, Value};
+
+/// two initialized keys; the first registered for round 9 and activated in epochs 9 and 10,
+/// with a snapshot of one key taken during epoch 9; the clock is in epoch 11
+async fn stored(name: &str) -> Arc<Shared> {
+    let keys = [([1u8; 32].to_vec(), 4), ([2u8; 32].to_vec(), 8)];
+    let db = common::databases(name, &keys).await;
+    let id = hex::encode([1; 32]);
+    for epoch in [9, 10] {
+        let atx = AtxInfo {
+            epoch,
+            atx_id: hex::encode([epoch as u8; 32]),
+            effective_num_units: 4,
+            coinbase: hex::encode([1; 24]),
+        };
+        db.save_atx(id.clone(), 4, atx).await.unwrap();
+    }
+    let registration = Registeration {
+        address: "https://poet.example".to_string(),
+        round_id: "9".to_string(),
+        round_end: 100,
+    };
+    db.save_poet(id, 4, registration).await.unwrap();
+    db.save_init_snapshot(9, 1, 4).await.unwrap();
+    let shared = common::shared(db, Config::default());
+    shared.clock.set(ClockState {
+        epoch: 11,
+        layer: 11 * 4032,
+        refreshed_at: unix_now(),
+    });
+    shared.node.set(NodeState {
+        connected_peers: 8,
+        synced: true,
+        synced_layer: 11 * 4032,
+        top_layer: 11 * 4032,
+        verified_layer: 11 * 4032 - 1,
+    });
+    shared
+}
+
+async fn overview(shared: &Arc<Shared>, epoch: Option<i64>, round: Option<i64>) -> Value {
+    let query = OverviewQuery { epoch, round };
+    let response = overview_handler(State(shared.clone()), Ok(Query(query)))
+        .await
+        .unwrap();
+    assert!(!response.partial);
+    serde_json::to_value(response.data).unwrap()
+}
+
+async fn error(shared: &Arc<Shared>, epoch: Option<i64>, round: Option<i64>) -> StatusCode {
+    let query = OverviewQuery { epoch, round };
+    overview_handler(State(shared.clone()), Ok(Query(query)))
+        .await
+        .unwrap_err()
+        .into_response()
+        .status()
+}
+
+#[tokio::test]
+async fn reports_the_previous_epoch_live() {
+    let shared = stored("overview-live").await;
+    let live = overview(&shared, None, None).await;
+    assert_eq!(live["epoch"], 10);
+    // every key initialized now, not the snapshot
+    assert_eq!(live["init_posted"], json!({"count": 2, "num_units": 12}));
+    assert_eq!(live["initializing"], json!({"count": 0, "num_units": 0}));
+    assert_eq!(live["actived"]["current"]["count"], 1);
+    assert_eq!(live["actived"]["next"]["count"], 0);
+    assert_eq!(live["node"]["synced"], true);
+    assert_eq!(live["unsynced"], false);
+}
+
+#[tokio::test]
+async fn reports_a_stored_epoch_or_round() {
+    let shared = stored("overview-stored").await;
+    let past = overview(&shared, Some(9), None).await;
+    assert_eq!(past["epoch"], 9);
+    assert_eq!(past["init_posted"], json!({"count": 1, "num_units": 4}));
+    assert_eq!(
+        past["registerd"],
+        json!({
+            "current": {"count": 1, "num_units": 4},
+            "next": {"count": 0, "num_units": 0},
+        })
+    );
+    assert_eq!(
+        past["actived"],
+        json!({
+            "current": {"count": 1, "num_units": 4},
+            "next": {"count": 1, "num_units": 4},
+        })
+    );
+    // what is known now says nothing about the past
+    assert_eq!(past["initializing"], Value::Null);
+    assert_eq!(past["node"], Value::Null);
+    assert_eq!(past["unsynced"], Value::Null);
+
+    assert_eq!(overview(&shared, None, Some(9)).await, past);
+    // no snapshot was taken while epoch 10 was current
+    assert_eq!(
+        overview(&shared, Some(10), None).await["init_posted"],
+        Value::Null
+    );
+}
+
+#[tokio::test]
+async fn rejects_both_or_unknown_epochs() {
+    let shared = stored("overview-errors").await;
+    assert_eq!(
+        error(&shared, Some(9), Some(9)).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(error(&shared, Some(3), None).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&shared, None, Some(3)).await, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,217 @@
+mod common;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    middleware,
+    response::Response,
+    routing::get,
+    Router,
+};
+use poolstats::{
+    auth::{require_scope, Scope, API_KEY_HEADER},
+    config::Config,
+    ratelimit::{
+        limit_auth_failures, rate_limit, RateLimiter, RouteBurst, AUTH_ROUTE, MAX_BUCKETS,
+    },
+};
+use tower::ServiceExt;
+
+fn burst(route: &str, burst: u32) -> RouteBurst {
+    RouteBurst {
+        route: route.to_string(),
+        burst,
+    }
+}
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn bursts_then_refills() {
+    let limiter = RateLimiter::new(2.0, 3, vec![]);
+    let t0 = Instant::now();
+    for _ in 0..3 {
+        assert_eq!(limiter.check_at("a", "/overview", t0), Ok(()));
+    }
+    assert_eq!(limiter.check_at("a", "/overview", t0), Err(ms(500)));
+    // other clients and routes have buckets of their own
+    assert_eq!(limiter.check_at("b", "/overview", t0), Ok(()));
+    assert_eq!(limiter.check_at("a", "/timeline", t0), Ok(()));
+
+    assert_eq!(
+        limiter.check_at("a", "/overview", t0 + ms(250)),
+        Err(ms(250))
+    );
+    assert_eq!(limiter.check_at("a", "/overview", t0 + ms(500)), Ok(()));
+    assert_eq!(
+        limiter.check_at("a", "/overview", t0 + ms(500)),
+        Err(ms(500))
+    );
+
+    // idle time refills up to the burst, not beyond
+    let later = t0 + Duration::from_secs(60);
+    for _ in 0..3 {
+        assert_eq!(limiter.check_at("a", "/overview", later), Ok(()));
+    }
+    assert!(limiter.check_at("a", "/overview", later).is_err());
+}
+
+#[test]
+fn routes_override_the_burst() {
+    let limiter = RateLimiter::new(1.0, 5, vec![burst("/nodes_info", 1)]);
+    let t0 = Instant::now();
+    assert_eq!(limiter.check_at("a", "/nodes_info", t0), Ok(()));
+    assert_eq!(limiter.check_at("a", "/nodes_info", t0), Err(ms(1000)));
+    for _ in 0..5 {
+        assert_eq!(limiter.check_at("a", "/overview", t0), Ok(()));
+    }
+    assert!(limiter.check_at("a", "/overview", t0).is_err());
+}
+
+#[test]
+fn waiting_takes_no_token() {
+    let limiter = RateLimiter::new(1.0, 1, vec![]);
+    let t0 = Instant::now();
+    for _ in 0..3 {
+        assert_eq!(limiter.wait_at("a", AUTH_ROUTE, t0), Ok(()));
+    }
+    assert_eq!(limiter.check_at("a", AUTH_ROUTE, t0), Ok(()));
+    assert_eq!(limiter.wait_at("a", AUTH_ROUTE, t0), Err(ms(1000)));
+}
+
+#[test]
+fn prunes_full_buckets_by_their_own_burst() {
+    let limiter = RateLimiter::new(1.0, 1, vec![burst("/export/nodes", 50)]);
+    let t0 = Instant::now();
+    for i in 0..MAX_BUCKETS - 1 {
+        assert_eq!(limiter.check_at(&i.to_string(), "/overview", t0), Ok(()));
+    }
+    for _ in 0..40 {
+        assert_eq!(limiter.check_at("a", "/export/nodes", t0), Ok(()));
+    }
+    // full again after a second, unlike the bucket with 12 of 50 tokens
+    let later = t0 + Duration::from_secs(2);
+    assert_eq!(limiter.check_at("new", "/overview", later), Ok(()));
+    for _ in 0..12 {
+        assert_eq!(limiter.check_at("a", "/export/nodes", later), Ok(()));
+    }
+    assert!(limiter.check_at("a", "/export/nodes", later).is_err());
+}
+
+#[test]
+fn drops_the_least_recently_used_when_full() {
+    let limiter = RateLimiter::new(0.001, 1, vec![]);
+    let t0 = Instant::now();
+    for i in 0..=MAX_BUCKETS {
+        let now = t0 + ms(i as u64);
+        assert_eq!(limiter.check_at(&i.to_string(), "/overview", now), Ok(()));
+    }
+    let now = t0 + ms(MAX_BUCKETS as u64 + 1);
+    // the newest half is kept, the oldest start over
+    assert!(limiter
+        .check_at(&MAX_BUCKETS.to_string(), "/overview", now)
+        .is_err());
+    assert_eq!(limiter.check_at("0", "/overview", now), Ok(()));
+}
+
+async fn send(router: &Router, ip: &str, key: Option<&str>) -> Response {
+    let mut request = Request::get("/overview").header("x-forwarded-for", ip);
+    if let Some(key) = key {
+        request = request.header(API_KEY_HEADER, key);
+    }
+    let request = request.body(Body::empty()).unwrap();
+    router.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn tells_when_to_retry() {
+    let limiter = RateLimiter::new(0.25, 1, vec![]);
+    let router = Router::new()
+        .route("/overview", get(|| async { "ok" }))
+        .route_layer(middleware::from_fn_with_state(limiter, rate_limit));
+    assert_eq!(
+        send(&router, "10.0.0.1", None).await.status(),
+        StatusCode::OK
+    );
+    let response = send(&router, "10.0.0.1", None).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "4");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"], "rate_limited");
+    assert_eq!(body["code"], 429);
+    assert_eq!(
+        send(&router, "10.0.0.2", None).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn ignores_entries_the_client_forwarded() {
+    let limiter = RateLimiter::new(0.001, 1, vec![]);
+    let router = Router::new()
+        .route("/overview", get(|| async { "ok" }))
+        .route_layer(middleware::from_fn_with_state(limiter, rate_limit));
+    // the proxy appends the address it saw to whatever the client sent
+    assert_eq!(
+        send(&router, "1.2.3.4, 10.0.0.1", None).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&router, "5.6.7.8, 10.0.0.1", None).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    let request = Request::get("/overview")
+        .header("x-forwarded-for", "10.0.0.1")
+        .header("x-real-ip", "10.0.0.2")
+        .body(Body::empty())
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn limits_failed_authentications_before_the_lookup() {
+    let shared = common::shared(
+        common::databases("auth-failures", &[]).await,
+        Config::default(),
+    );
+    let key = shared
+        .db_handler
+        .create_api_key("pool".to_string(), Scope::Private)
+        .await
+        .unwrap()
+        .key;
+    let limiter = RateLimiter::new(0.001, 10, vec![burst(AUTH_ROUTE, 2)]);
+    let router = Router::new()
+        .route("/overview", get(|| async { "ok" }))
+        .route_layer(middleware::from_fn_with_state(
+            (Arc::clone(&shared), Scope::Private),
+            require_scope,
+        ))
+        .route_layer(middleware::from_fn_with_state(limiter, limit_auth_failures));
+
+    // valid keys take nothing from the bucket
+    for _ in 0..3 {
+        let response = send(&router, "10.0.0.1", Some(&key)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    for unknown in ["ps_1", "ps_2"] {
+        let response = send(&router, "10.0.0.1", Some(unknown)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    for key in [Some("ps_3"), Some(key.as_str()), None] {
+        let response = send(&router, "10.0.0.1", key).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+    }
+    let response = send(&router, "10.0.0.2", Some("ps_3")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
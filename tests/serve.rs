@@ -0,0 +1,128 @@
+mod common;
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Request, StatusCode},
+    routing::get,
+    Router,
+};
+use poolstats::serve::{cors_layer, serve, Listen, ReloadingCert};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    time::sleep,
+};
+use tower::ServiceExt;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/common/tls")
+        .join(name)
+}
+
+fn der(cert: &Path) -> Vec<u8> {
+    let mut reader = BufReader::new(File::open(cert).unwrap());
+    let first = rustls_pemfile::certs(&mut reader).next().unwrap().unwrap();
+    first.to_vec()
+}
+
+fn served(reloading: &ReloadingCert) -> Vec<u8> {
+    reloading.current().cert[0].to_vec()
+}
+
+/// copies a fixture pair over `cert` and `key`, dated `age` ago
+fn install(pair: &str, cert: &Path, key: &Path, age: Duration) {
+    std::fs::copy(fixture(&format!("{}.crt", pair)), cert).unwrap();
+    std::fs::copy(fixture(&format!("{}.key", pair)), key).unwrap();
+    let modified = SystemTime::now() - age;
+    for path in [cert, key] {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+}
+
+#[test]
+fn reloads_a_changed_certificate() {
+    let dir = common::temp_dir("tls-reload");
+    let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+    install("first", &cert, &key, Duration::from_secs(60));
+    let reloading = ReloadingCert::load(cert.clone(), key.clone()).unwrap();
+    assert_eq!(served(&reloading), der(&fixture("first.crt")));
+
+    // unchanged files are not read again
+    reloading.reload_if_changed();
+    assert_eq!(served(&reloading), der(&fixture("first.crt")));
+
+    install("second", &cert, &key, Duration::from_secs(30));
+    reloading.reload_if_changed();
+    assert_eq!(served(&reloading), der(&fixture("second.crt")));
+
+    // a broken renewal keeps the previous pair
+    std::fs::write(&cert, "not a certificate").unwrap();
+    reloading.reload_if_changed();
+    assert_eq!(served(&reloading), der(&fixture("second.crt")));
+}
+
+#[tokio::test]
+async fn serves_on_a_unix_socket() {
+    let dir = common::temp_dir("unix-socket");
+    let path = dir.join("api.sock");
+    // left behind by a previous run
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    let router = Router::new().route("/healthz", get(|| async { "ok" }));
+    tokio::spawn(serve(router, Listen::Unix(path.clone()), None));
+
+    let mut stream = loop {
+        match UnixStream::connect(&path).await {
+            Ok(stream) => break stream,
+            Err(_) => sleep(Duration::from_millis(20)).await,
+        }
+    };
+    stream
+        .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("ok"), "{}", response);
+}
+
+#[tokio::test]
+async fn allows_only_configured_origins() {
+    let allowed = HeaderValue::from_static("https://pool.example");
+    let router = Router::new()
+        .route("/overview", get(|| async { "ok" }))
+        .layer(cors_layer(std::slice::from_ref(&allowed), &[]));
+    let send = |origin: &'static str| {
+        let request = Request::get("/overview")
+            .header(header::ORIGIN, origin)
+            .body(Body::empty())
+            .unwrap();
+        router.clone().oneshot(request)
+    };
+
+    let response = send("https://pool.example").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+        Some(&allowed)
+    );
+
+    let response = send("https://elsewhere.example").await.unwrap();
+    assert!(response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use poolstats::rpc::{PostSetup, PostSetupState, Smesher};
+
+fn smesher(i: u8, state: PostSetupState) -> Smesher {
+    Smesher {
+        id: hex::encode([i; 32]),
+        smeshing: state == PostSetupState::Complete,
+        post: PostSetup {
+            state,
+            num_labels_written: 1024,
+            num_units: 4,
+        },
+    }
+}
+
+#[tokio::test]
+async fn forgets_identities_a_node_stopped_reporting() {
+    let db = common::databases("smeshers", &[]).await;
+    let (a, b) = ("10.0.0.1:9093".to_string(), "10.0.0.2:9093".to_string());
+    let in_progress = PostSetupState::InProgress;
+    db.save_smesher_states(&a, &[smesher(1, in_progress), smesher(2, in_progress)], 100)
+        .await
+        .unwrap();
+    db.save_smesher_states(&b, &[smesher(3, in_progress)], 100)
+        .await
+        .unwrap();
+    assert_eq!(db.count_initializing().await.unwrap(), 3);
+    assert_eq!(db.initializing_num_units().await.unwrap(), 12);
+
+    let complete = [smesher(1, PostSetupState::Complete)];
+    assert_eq!(db.save_smesher_states(&a, &complete, 200).await.unwrap(), 2);
+    // the same report again changes nothing
+    assert_eq!(db.save_smesher_states(&a, &complete, 250).await.unwrap(), 0);
+    let states = db.get_smesher_states().await.unwrap();
+    assert_eq!(states.len(), 2);
+    assert_eq!(
+        states[&hex::encode([1; 32])].state,
+        PostSetupState::Complete
+    );
+    assert_eq!(states[&hex::encode([1; 32])].updated_at, 200);
+    assert!(!states.contains_key(&hex::encode([2; 32])));
+    // the other node's identities are untouched
+    assert_eq!(states[&hex::encode([3; 32])].updated_at, 100);
+    assert_eq!(db.count_initializing().await.unwrap(), 1);
+
+    // a node removed from the config
+    assert_eq!(
+        db.forget_smesher_nodes(std::slice::from_ref(&a))
+            .await
+            .unwrap(),
+        1
+    );
+    let states = db.get_smesher_states().await.unwrap();
+    assert_eq!(states.len(), 1);
+    assert_eq!(db.count_initializing().await.unwrap(), 0);
+
+    assert_eq!(db.save_smesher_states(&a, &[], 300).await.unwrap(), 1);
+    assert!(db.get_smesher_states().await.unwrap().is_empty());
+}
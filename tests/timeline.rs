@@ -0,0 +1,61 @@
+use axum::http::StatusCode;
+use poolstats::timeline::{timeline_range, TimelineQuery, MAX_TIMELINE_EPOCHS};
+
+fn range(from: Option<i64>, to: Option<i64>) -> Result<(i64, i64), (StatusCode, String)> {
+    timeline_range(&TimelineQuery { from, to }, 3, 10, MAX_TIMELINE_EPOCHS)
+        .map_err(|e| (e.status, e.message))
+}
+
+#[test]
+fn defaults_to_the_stored_epochs() {
+    assert_eq!(range(None, None), Ok((3, 10)));
+    assert_eq!(range(Some(5), None), Ok((5, 10)));
+    assert_eq!(range(None, Some(7)), Ok((3, 7)));
+    assert_eq!(range(None, Some(5000)), Ok((4001, 5000)));
+}
+
+#[test]
+fn accepts_the_widest_range() {
+    let last = MAX_TIMELINE_EPOCHS - 1;
+    assert_eq!(range(Some(0), Some(last)), Ok((0, last)));
+    assert_eq!(range(Some(9), Some(9)), Ok((9, 9)));
+}
+
+#[test]
+fn rejects_reversed_and_wide_ranges() {
+    let reversed = range(Some(10), Some(9)).unwrap_err();
+    assert_eq!(
+        reversed,
+        (StatusCode::BAD_REQUEST, "from is after to".to_string())
+    );
+    let too_wide = (
+        StatusCode::BAD_REQUEST,
+        format!("at most {} epochs per request", MAX_TIMELINE_EPOCHS),
+    );
+    assert_eq!(
+        range(Some(0), Some(MAX_TIMELINE_EPOCHS)),
+        Err(too_wide.clone())
+    );
+    assert_eq!(range(Some(-1000), None), Err(too_wide.clone()));
+}
+
+#[test]
+fn rejects_bounds_that_overflow() {
+    let too_wide = Err((
+        StatusCode::BAD_REQUEST,
+        format!("at most {} epochs per request", MAX_TIMELINE_EPOCHS),
+    ));
+    assert_eq!(range(Some(i64::MIN), Some(i64::MAX)), too_wide);
+    assert_eq!(range(Some(i64::MIN), None), too_wide);
+    assert_eq!(range(Some(-1), Some(i64::MAX)), too_wide);
+    assert_eq!(
+        range(None, Some(i64::MIN)),
+        Err((StatusCode::BAD_REQUEST, "from is after to".to_string()))
+    );
+    let last = i64::MAX - MAX_TIMELINE_EPOCHS + 1;
+    assert_eq!(range(None, Some(i64::MAX)), Ok((last, i64::MAX)));
+    assert_eq!(
+        range(Some(i64::MIN), Some(i64::MIN)),
+        Ok((i64::MIN, i64::MIN))
+    );
+}